@@ -0,0 +1,89 @@
+// Compare identifying paths with a linear scan of every suite against the
+// prebuilt suite index for a tree with thousands of manifests.
+extern crate test;
+extern crate liboverlord;
+
+use liboverlord::config::{Suite, SuitePath, ExitCodeFormat};
+use liboverlord::path_identifier::{identify};
+use liboverlord::suite_index::{SuiteIndex};
+use liboverlord::util::{PathWrapper};
+use test::{Bencher};
+
+fn get_suite<'a>(root: String, path: &str) -> Suite<'a> {
+  let root = PathWrapper::new(Path::new(root));
+  let paths = vec![SuitePath::new(&root, path.to_string())];
+  Suite {
+    group: "unit".to_string(),
+    name: None,
+    title: None,
+    manifest: PathWrapper::new(root.get().join("overlord.toml")),
+    manifest_index: 0,
+    exclude: Vec::new(),
+    respect_ignore_files: true,
+    root: root,
+    paths: paths,
+    executable: "node".to_string(),
+    format: ExitCodeFormat
+  }
+}
+
+// Every project has a top level suite (for integration tests anywhere in the
+// project) and a suite in each of its components (like a monorepo where every
+// directory has its own manifest).
+fn get_suites<'a>() -> Vec<Suite<'a>> {
+  let mut suites = Vec::new();
+  for project in range(0u, 100) {
+    suites.push(get_suite(
+      format!("/repo/project{}", project), "**/*_integration.js"
+    ));
+    for component in range(0u, 20) {
+      suites.push(get_suite(
+        format!("/repo/project{}/component{}", project, component),
+        "*_test.js"
+      ));
+    }
+  }
+  suites
+}
+
+// Half of the paths belong to a component suite and the other half fall back
+// to the top level suite of their project.
+fn get_paths() -> Vec<Path> {
+  range(0u, 100).map(|project| {
+    let kind = if project % 2 == 0 { "test" } else { "integration" };
+    Path::new(format!(
+      "/repo/project{}/component{}/thing_{}.js", project, project % 20, kind
+    ))
+  }).collect()
+}
+
+#[bench]
+fn identify_linear(b: &mut Bencher) {
+  let suites = get_suites();
+  let paths = get_paths();
+  b.iter(|| {
+    for path in paths.iter() {
+      assert!(identify(path, &suites).is_some());
+    }
+  });
+}
+
+#[bench]
+fn identify_indexed(b: &mut Bencher) {
+  let suites = get_suites();
+  let index = SuiteIndex::new(&suites);
+  let paths = get_paths();
+  b.iter(|| {
+    for path in paths.iter() {
+      assert!(index.identify(path).is_some());
+    }
+  });
+}
+
+#[bench]
+fn build_index(b: &mut Bencher) {
+  let suites = get_suites();
+  b.iter(|| {
+    SuiteIndex::new(&suites)
+  });
+}
@@ -1,153 +1,501 @@
-//#![feature(macro_rules)]
-//#![feature(struct_variant)]
-
-//extern crate liboverlord;
-//extern crate getopts;
-//use std::os;
-//use std::io::stdio;
-//use liboverlord::config::{Config};
-//use liboverlord::error::{OverlordResult, OverlordError};
-//use liboverlord::suite::{Suite};
-//use getopts::{optopt, optflag, getopts, OptGroup, usage, Matches};
-
-//static CONFIG_PATH: &'static str = "overlord.toml";
-
-//// Macro which returns from the current function and exits the process with an
-//// error code of 1.
-////
-//// ```
-//// # #![allow(unreachable_code)]
-//// fn main(soup: bool) {
-////   // Soup nazi does not like you therefore you never can have soup.
-////   if soup {
-////     exit!("{} bad!", "No soup for you");
-////     // exit! returns and sets the exit code to 1.
-////     println!("Never reached");
-////   }
-//// }
-//// ```
-//macro_rules! exit {
-  //($($arg:tt)*) => ({
-    //let mut stderr = stdio::stderr();
-    //let output = format_args!(std::fmt::format, $($arg)*);
-    //match stderr.write_str(output.as_slice()) {
-      //Ok(_) => return os::set_exit_status(1),
-      //Err(e) => fail!("Failed writing during exit! macro. {}", e)
-    //}
-  //});
-//}
-
-//// Defined as a static mostly so indentation looks correct.
-//static SUBCMD: &'static str = r#"
-
-//Subcommands:
-    //suites: List all available suites.
-    //help: Show this help message.
-    //run: Determine which suite a file belongs to an run it.
-//"#;
-
-//struct CLI<'a> {
-  //program: String,
-  //args: Vec<String>,
-  //opts: Vec<OptGroup>,
-  //matches: Matches
-//}
-
-//impl<'a> CLI<'a> {
-  //pub fn opts() -> Vec<OptGroup> {
-    //vec!(
-      //optflag("h", "help", "Show help for top level options"),
-      //optopt("c", "config", "TOML configuration file", CONFIG_PATH),
-      //optopt("C", "cwd", "Current working directory", "<path>")
-    //)
-  //}
-
-  //pub fn new(args: Vec<String>) -> CLI {
-    //let opts = CLI::opts();
-    //CLI {
-      //program: args.get(0).to_string(),
-      //opts: opts.clone(),
-      //args: args.clone(),
-      //matches: getopts(args.tail(), opts.as_slice()).unwrap()
-    //}
-  //}
-
-  //pub fn usage(&self) -> String {
-    //let header = format!("{} [TODO]", self.program);
-    //let cmds = getopts::usage(header.as_slice(), self.opts.as_slice());
-    //return format!("{} {}", cmds, SUBCMD);
-  //}
-
-  //fn config_path(&self) -> OverlordResult<Path> {
-    //let cwd = match self.matches.opt_str("C") {
-      //Some(v) => Path::new(v),
-      //None => os::getcwd()
-    //};
-
-    //// Get the configuration path from the flags.
-    //// Ensure the configuration path was found.
-    //let config_opt = cwd.join(match self.matches.opt_str("c") {
-      //Some(v) => v,
-      //None => CONFIG_PATH.to_string(),
-    //});
-
-    //let config_path = Path::new(config_opt);
-    //if config_path.exists() {
-      //Ok(config_path)
-    //} else {
-      //Err(OverlordError::new(format!(
-        //"Configuration path does not exist: \"{}\" \n\n {}",
-        //config_path.display(), self.usage()
-      //)))
-    //}
-  //}
-
-  //fn load_config(&self) -> OverlordResult<Config> {
-    //let path = try!(self.config_path());
-    //Config::parse(&path)
-  //}
-
-  //fn cmd_help(&self) -> OverlordResult<()> {
-    //println!("{}", self.usage());
-    //Ok(())
-  //}
-
-  //fn cmd_suites(&self, config: &Config) -> OverlordResult<()> {
-    //// TODO: Figure out how to generate machine readable output?
-    //println!("Available suites:")
-    //for (name, suite) in config.manifest.suites.iter() {
-      //println!("    {}", name);
-    //}
-    //println!("")
-    //Ok(())
-  //}
-
-  //pub fn run<'a>(&'a self) -> OverlordResult<()> {
-    //let config = try!(self.load_config());
-
-    //if self.matches.free.len() < 1 {
-      //return Err(OverlordError::new("No subcommand provided.".to_string()));
-    //}
-
-    //let subcommand = self.matches.free.get(0).as_slice();
-    //match subcommand {
-      //"suites" => self.cmd_suites(&config),
-      //"help" => self.cmd_help(),
-      //_ => Err(OverlordError::new("Unknown subcommand".to_string()))
-    //}
-  //}
-//}
-
-//fn main() {
-  //let cli = CLI::new(os::args());
-  //match cli.run() {
-    //// Every error must be an OverlordError here.
-    //Err(e) => exit!("{} \n\n {}", e.human_error(), cli.usage()),
-    //// Success we don't care about the output here.
-    //_ => return
-  //}
-//}
+#![feature(macro_rules)]
+
+extern crate liboverlord;
+extern crate getopts;
+use std::os;
+use std::io::stdio;
+use std::io::fs::{PathExtensions};
+use liboverlord::config::{Suite, suite_by_name, select_group};
+use liboverlord::config_loader::{import_with, ImportOptions};
+use liboverlord::consts::{MANIFEST};
+use liboverlord::error::{OverlordResult, OverlordError};
+use liboverlord::file_finder::{find_files};
+use liboverlord::suite_index::{SuiteIndex};
+use liboverlord::reporter::{Reporter, for_name};
+use liboverlord::merger::{Merger};
+use liboverlord::scheduler::{Scheduler, Job, SuiteFileResult, RunEvent,
+                             JobStarted, JobTest, JobFinished,
+                             expand_suites};
+use getopts::{optopt, optmulti, optflag, getopts, OptGroup, Matches};
+
+// Macro which returns from the current function and exits the process with an
+// error code of 1.
+//
+// ```
+// # #![allow(unreachable_code)]
+// fn main(soup: bool) {
+//   // Soup nazi does not like you therefore you never can have soup.
+//   if soup {
+//     exit!("{} bad!", "No soup for you");
+//     // exit! returns and sets the exit code to 1.
+//     println!("Never reached");
+//   }
+// }
+// ```
+macro_rules! exit {
+  ($($arg:tt)*) => ({
+    let mut stderr = stdio::stderr();
+    let output = format_args!(std::fmt::format, $($arg)*);
+    match stderr.write_line(output.as_slice()) {
+      Ok(_) => return os::set_exit_status(1),
+      Err(e) => fail!("Failed writing during exit! macro. {}", e)
+    }
+  });
+}
+
+// Defined as a static mostly so indentation looks correct.
+static SUBCMD: &'static str = r#"
+Subcommands:
+    suites: List all available suites.
+    which <path>...: Show which suite each path belongs to.
+    files: List the files for all (or the selected) suites.
+    run [<path>...]: Run the given files or all (or the selected) suites.
+    help: Show this help message.
+"#;
+
+struct CLI {
+  program: String,
+  opts: Vec<OptGroup>,
+  matches: Matches
+}
+
+impl CLI {
+  pub fn opts() -> Vec<OptGroup> {
+    vec!(
+      optflag("h", "help", "Show help for top level options"),
+      optopt("c", "config", "TOML configuration file", MANIFEST),
+      optopt("C", "cwd", "Current working directory", "<path>"),
+      optmulti(
+        "s",
+        "suite",
+        "Name or index of suite (from `suites`) to use",
+        "<name>"
+      ),
+      optmulti(
+        "g",
+        "group",
+        "Group of suites (from any manifest) to use",
+        "<name>"
+      ),
+      optflag("", "strict", "Fail on circular manifest imports"),
+      optopt("j", "jobs", "Number of files to run in parallel", "<n>"),
+      optopt(
+        "r",
+        "reporter",
+        "Output format of `run` (json, junit, pretty, tap, tbpl)",
+        "<name>"
+      )
+    )
+  }
+
+  pub fn new(args: Vec<String>) -> OverlordResult<CLI> {
+    let opts = CLI::opts();
+    let matches = match getopts(args.tail(), opts.as_slice()) {
+      Ok(v) => v,
+      Err(e) => return Err(OverlordError::new(format!("{}", e)))
+    };
+
+    Ok(CLI {
+      program: args[0].clone(),
+      opts: opts,
+      matches: matches
+    })
+  }
+
+  pub fn usage(&self) -> String {
+    let header = format!("{} [options] <subcommand>", self.program);
+    let cmds = getopts::usage(header.as_slice(), self.opts.as_slice());
+    return format!("{} {}", cmds, SUBCMD);
+  }
+
+  fn cwd(&self) -> Path {
+    let cwd = match self.matches.opt_str("C") {
+      Some(v) => Path::new(v),
+      None => os::getcwd()
+    };
+    os::make_absolute(&cwd)
+  }
+
+  fn config_path(&self) -> OverlordResult<Path> {
+    // Get the configuration path from the flags.
+    let config_path = self.cwd().join(match self.matches.opt_str("c") {
+      Some(v) => v,
+      None => MANIFEST.to_string(),
+    });
+
+    // Ensure the configuration path was found.
+    if config_path.exists() {
+      Ok(config_path)
+    } else {
+      Err(OverlordError::new(format!(
+        "Configuration path does not exist: \"{}\"", config_path.display()
+      )))
+    }
+  }
+
+  fn load_suites(&self) -> OverlordResult<Vec<Suite>> {
+    let path = try!(self.config_path());
+    let options = ImportOptions { strict: self.matches.opt_present("strict") };
+    import_with(path, &options, |warning| {
+      let mut stderr = stdio::stderr();
+      let message = format!("Warning: {}", warning.message);
+      match stderr.write_line(message.as_slice()) {
+        Ok(_) => {},
+        Err(e) => fail!("Failed writing warning. {}", e)
+      }
+    })
+  }
+
+  // Arguments after the subcommand resolved relative to the cwd.
+  fn path_args(&self) -> Vec<Path> {
+    let cwd = self.cwd();
+    self.matches.free.tail().iter().map(|arg| {
+      cwd.join(arg.as_slice())
+    }).collect()
+  }
+
+  // Indexes of the suites selected with --suite and --group (or all suites).
+  fn selected_suites(
+    &self, suites: &Vec<Suite>
+  ) -> OverlordResult<Vec<uint>> {
+    let selected = self.matches.opt_strs("s");
+    let groups = self.matches.opt_strs("g");
+    if selected.len() == 0 && groups.len() == 0 {
+      return Ok(range(0, suites.len()).collect());
+    }
+
+    let mut indexes = Vec::new();
+    for group in groups.iter() {
+      indexes.push_all_move(try!(select_group(suites, group.as_slice())));
+    }
+
+    for value in selected.iter() {
+      // Names take precedence over indexes.
+      match suite_by_name(suites, value.as_slice()) {
+        Some(suite) => {
+          indexes.push(suite_index(suites, suite));
+          continue;
+        },
+        None => {}
+      }
+
+      match from_str::<uint>(value.as_slice()) {
+        Some(index) if index < suites.len() => indexes.push(index),
+        _ => {
+          return Err(OverlordError::new(format!(
+            "Unknown suite: '{}'", value
+          )));
+        }
+      }
+    }
+
+    // Suites selected more than once are still only run once.
+    indexes.sort();
+    indexes.dedup();
+    Ok(indexes)
+  }
+
+  fn scheduler(&self) -> OverlordResult<Scheduler> {
+    match self.matches.opt_str("j") {
+      None => Ok(Scheduler::new()),
+      Some(value) => match from_str::<uint>(value.as_slice()) {
+        Some(jobs) => Ok(Scheduler::with_jobs(jobs)),
+        None => Err(OverlordError::new(format!(
+          "Invalid number of jobs: '{}'", value
+        )))
+      }
+    }
+  }
+
+  fn reporter(&self) -> OverlordResult<Option<Box<Reporter>>> {
+    match self.matches.opt_str("r") {
+      None => Ok(None),
+      Some(name) => match for_name(name.as_slice()) {
+        Some(reporter) => Ok(Some(reporter)),
+        None => Err(OverlordError::new(format!(
+          "Unknown reporter: '{}'", name
+        )))
+      }
+    }
+  }
+
+  // Paths are shown relative to the cwd where possible.
+  fn display_path(&self, path: &Path) -> String {
+    let relative = path.path_relative_from(&self.cwd());
+    let shown = relative.as_ref().unwrap_or(path);
+    format!("{}", shown.display())
+  }
+
+  fn cmd_help(&self) -> OverlordResult<()> {
+    println!("{}", self.usage());
+    Ok(())
+  }
+
+  fn cmd_suites(&self, suites: &Vec<Suite>) -> OverlordResult<()> {
+    println!("Available suites:");
+    for (index, suite) in suites.iter().enumerate() {
+      let title = match suite.title {
+        Some(ref title) => format!(" \"{}\"", title),
+        None => String::new()
+      };
+      let group = match suite.name {
+        Some(_) => format!(" [{}]", suite.group),
+        None => String::new()
+      };
+      println!(
+        "    {}: {}{}{} {} ({}) defined in {} (#{})",
+        index,
+        suite.label(),
+        group,
+        title,
+        self.display_path(suite.root.get()),
+        suite.executable,
+        self.display_path(suite.manifest.get()),
+        suite.manifest_index
+      );
+    }
+    println!("");
+    Ok(())
+  }
+
+  fn cmd_which(&self, suites: &Vec<Suite>) -> OverlordResult<()> {
+    let paths = self.path_args();
+    if paths.len() == 0 {
+      return Err(OverlordError::new("No paths given to `which`".to_string()));
+    }
+
+    let index = SuiteIndex::new(suites);
+    for path in paths.iter() {
+      match index.identify(path) {
+        Some(suite) => println!(
+          "{}: {} {} (defined in {})",
+          self.display_path(path),
+          suite_index(suites, suite),
+          suite.label(),
+          self.display_path(suite.manifest.get())
+        ),
+        None => println!("{}: no suite", self.display_path(path))
+      }
+    }
+    Ok(())
+  }
+
+  fn cmd_files(&self, suites: &Vec<Suite>) -> OverlordResult<()> {
+    for index in try!(self.selected_suites(suites)).iter() {
+      for file in try!(find_files(&suites[*index])).iter() {
+        println!("{}", self.display_path(file));
+      }
+    }
+    Ok(())
+  }
+
+  // Jobs for the explicitly given files or for every file in the selected
+  // suites. Explicit files are run by the deepest selected suite which
+  // contains them.
+  fn jobs(&self, suites: &Vec<Suite>) -> OverlordResult<Vec<Job>> {
+    let selected = try!(self.selected_suites(suites));
+    let paths = self.path_args();
+    if paths.len() == 0 {
+      return expand_suites(suites, &selected);
+    }
+
+    let index = SuiteIndex::new(suites);
+    let mut jobs = Vec::new();
+    for path in paths.iter() {
+      let found = index.identify_all(path).move_iter().map(|suite| {
+        suite_index(suites, suite)
+      }).find(|position| selected.contains(position));
+
+      match found {
+        Some(suite) => jobs.push(Job { suite: suite, file: path.clone() }),
+        None => {
+          let kind = if self.matches.opt_present("s") ||
+                        self.matches.opt_present("g") {
+            "selected suite"
+          } else {
+            "suite"
+          };
+          return Err(OverlordError::new(format!(
+            "No {} found for: '{}'", kind, self.display_path(path)
+          )));
+        }
+      }
+    }
+    Ok(jobs)
+  }
+
+  // Default output of each event. Events are expected to be merged so the
+  // output of files running in parallel is never interleaved.
+  fn print_event(
+    &self, suites: &Vec<Suite>, jobs: &Vec<Job>, event: &RunEvent
+  ) {
+    match *event {
+      JobStarted(index) => {
+        let ref job = jobs[index];
+        println!(
+          "{} {}", suites[job.suite].group, self.display_path(&job.file)
+        );
+      },
+      JobTest(_, ref test) => {
+        let status = if test.failed() { "FAIL" } else { "ok" };
+        println!("    {} {}", status, test.name);
+      },
+      JobFinished(_, Ok(ref result)) => {
+        let status = if result.success() { "PASS" } else { "FAIL" };
+        println!("  {} ({}ms)", status, result.duration.num_milliseconds());
+        if !result.success() {
+          for line in result.stderr.as_slice().lines() {
+            println!("    {}", line);
+          }
+        }
+      },
+      JobFinished(_, Err(ref e)) => {
+        println!("  ERROR {}", e.clone().human_error());
+      }
+    }
+  }
+
+  fn print_summary(&self, results: &Vec<SuiteFileResult>) {
+    let failed = results.iter().filter(|file_result| {
+      !file_result.result.success()
+    }).count();
+
+    println!(
+      "\n{} files, {} passed, {} failed",
+      results.len(),
+      results.len() - failed,
+      failed
+    );
+  }
+
+  fn cmd_run(&self, suites: &Vec<Suite>) -> OverlordResult<()> {
+    let scheduler = try!(self.scheduler());
+    let reporter = try!(self.reporter());
+    let jobs = try!(self.jobs(suites));
+
+    let results = match reporter {
+      Some(reporter) => {
+        let results = try!(scheduler.run_jobs(suites, jobs));
+        let mut stdout = stdio::stdout();
+        match reporter.report(suites, &results, &mut stdout) {
+          Ok(_) => {},
+          Err(e) => {
+            return Err(OverlordError::new(format!(
+              "Failed writing report: {}", e.desc
+            )));
+          }
+        }
+        results
+      },
+      None => {
+        let mut merger = Merger::new();
+        let all_jobs = jobs.clone();
+        let results = try!(scheduler.run_jobs_with(suites, jobs, |event| {
+          for merged in merger.push(event.clone()).iter() {
+            self.print_event(suites, &all_jobs, merged);
+          }
+        }));
+        self.print_summary(&results);
+        results
+      }
+    };
+
+    let failed = results.iter().any(|file_result| {
+      file_result.result.test_results().iter().any(|test| test.failed())
+    });
+
+    if failed {
+      os::set_exit_status(1);
+    }
+    Ok(())
+  }
+
+  pub fn run(&self) -> OverlordResult<()> {
+    if self.matches.opt_present("h") {
+      return self.cmd_help();
+    }
+
+    if self.matches.free.len() < 1 {
+      return Err(OverlordError::new(format!(
+        "No subcommand provided.\n\n{}", self.usage()
+      )));
+    }
+
+    let subcommand = self.matches.free[0].as_slice();
+    if subcommand == "help" {
+      return self.cmd_help();
+    }
+
+    let suites = try!(self.load_suites());
+    match subcommand {
+      "suites" => self.cmd_suites(&suites),
+      "which" => self.cmd_which(&suites),
+      "files" => self.cmd_files(&suites),
+      "run" => self.cmd_run(&suites),
+      _ => Err(OverlordError::new(format!(
+        "Unknown subcommand: '{}'\n\n{}", subcommand, self.usage()
+      )))
+    }
+  }
+}
+
+// Position of a suite (as returned by identify) in the list of all suites.
+fn suite_index(suites: &Vec<Suite>, suite: &Suite) -> uint {
+  suites.iter().position(|candidate| {
+    candidate as *const Suite == suite as *const Suite
+  }).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{CLI};
+
+  fn get_cli(args: &[&str]) -> CLI {
+    let mut all = vec![
+      "overlord".to_string(), "-C".to_string(), "test/groups".to_string()
+    ];
+    for arg in args.iter() {
+      all.push(arg.to_string());
+    }
+    CLI::new(all).unwrap()
+  }
+
+  #[test]
+  fn run_paths_in_selected_group() {
+    let cli = get_cli(&["--group", "lint", "run", "a.txt"]);
+    let suites = cli.load_suites().unwrap();
+    let jobs = cli.jobs(&suites).unwrap();
+
+    // The unit suite of the same root would be found without the group.
+    assert_eq!(jobs.len(), 1);
+    assert_eq!(suites[jobs[0].suite].group, "lint".to_string());
+  }
+
+  #[test]
+  fn run_paths_outside_selected_suites() {
+    let cli = get_cli(&["--group", "unit", "-s", "2", "run", "a.txt"]);
+    let suites = cli.load_suites().unwrap();
+    let jobs = cli.jobs(&suites).unwrap();
+    assert_eq!(suites[jobs[0].suite].group, "unit".to_string());
+
+    let cli = get_cli(&["-s", "2", "run", "a.txt"]);
+    let error = cli.jobs(&suites).err().unwrap();
+    assert_eq!(
+      error.message, "No selected suite found for: 'a.txt'".to_string()
+    );
+  }
+}
 
 fn main() {
-  println!("beta");
+  let cli = match CLI::new(os::args()) {
+    Ok(v) => v,
+    Err(e) => exit!("{}", e.human_error())
+  };
+
+  match cli.run() {
+    // Every error must be an OverlordError here.
+    Err(e) => exit!("{}", e.human_error()),
+    // Success we don't care about the output here.
+    _ => return
+  }
 }
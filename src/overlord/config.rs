@@ -1,10 +1,71 @@
 // This module contains the he canonical interfaaces used by all internal
 // operations in overlord it may or may not conform to the same format as the
 // user facing interchange format.
+use error::{OverlordError, OverlordResult};
 use util::{PathWrapper};
 use glob::{Pattern, MatchOptions};
 use std::fmt::{FormatError, Formatter, Show};
 use std::cmp::{PartialEq};
+use std::from_str::{FromStr};
+
+// The format of the output of an executable which determines how (and if) the
+// output is parsed into individual tests.
+#[deriving(Clone, PartialEq, Show)]
+pub enum Format {
+  /// Output is not parsed and only the exit code is used (the default).
+  ExitCodeFormat,
+
+  /// Test Anything Protocol.
+  TapFormat,
+
+  /// Mozilla TBPL log lines.
+  TbplFormat,
+
+  /// Mozilla structured logging.
+  MozlogFormat,
+
+  /// Overlord JSON results (see design/json_results.md).
+  JsonFormat
+}
+
+/// Names of all formats as used in manifests.
+pub static FORMAT_NAMES: &'static [&'static str] =
+  &["exit_code", "tap", "tbpl", "mozlog", "json"];
+
+impl Format {
+  /// The name used in manifests.
+  pub fn name(&self) -> &'static str {
+    match *self {
+      ExitCodeFormat => "exit_code",
+      TapFormat => "tap",
+      TbplFormat => "tbpl",
+      MozlogFormat => "mozlog",
+      JsonFormat => "json"
+    }
+  }
+}
+
+impl FromStr for Format {
+  fn from_str(name: &str) -> Option<Format> {
+    match name {
+      "exit_code" => Some(ExitCodeFormat),
+      "tap" => Some(TapFormat),
+      "tbpl" => Some(TbplFormat),
+      "mozlog" => Some(MozlogFormat),
+      "json" => Some(JsonFormat),
+      _ => None
+    }
+  }
+}
+
+// A single component of a recursive suite path.
+pub enum PathComponent {
+  // `**` matches any number of directories (including none).
+  AnyDirectories,
+
+  // Glob which matches exactly one component.
+  ComponentGlob(Pattern)
+}
 
 // A suite "path" is a level of indirection around a glob pattern with show
 // funcitonality and a constructor geared towards overlord specific path
@@ -13,8 +74,16 @@ pub struct SuitePath {
   // Actual glob pattern used to match the path.
   pub pattern: Pattern,
 
+  // Components of the path (joined to the root) when the path contains a `**`
+  // component (recursive paths are matched one component at a time).
+  pub components: Option<Vec<PathComponent>>,
+
   // Human readable path
-  pub path: String
+  pub path: String,
+
+  // Directory the path is relative to. This is the root of the suite unless
+  // the path was added by a manifest extending the suite.
+  pub root: PathWrapper
 }
 
 impl SuitePath {
@@ -30,7 +99,74 @@ impl SuitePath {
       // unwrap is somewhat lazy here but doing this "correctly" is more work
       // and make the signature uglier.
       pattern: Pattern::new(joined_str),
-      path: joined_path
+      components: recursive_components(joined_str),
+      path: joined_path,
+      root: root.clone()
+    }
+  }
+
+  /// Determine if the path matches. Paths without a `**` component never
+  /// match into subdirectories.
+  pub fn matches(&self, path: &Path, options: MatchOptions) -> bool {
+    let components = match self.components {
+      Some(ref components) => components,
+      None => return self.pattern.matches_path_with(path, options)
+    };
+
+    // The components are of the joined path so absolute paths (which may not
+    // be inside of the root) match as well.
+    if path.is_absolute() != Path::new(self.path.as_slice()).is_absolute() {
+      return false
+    }
+
+    let parts: Vec<Option<&str>> = path.str_components().collect();
+    if parts.iter().any(|part| part.is_none()) {
+      return false
+    }
+
+    let parts: Vec<&str> = parts.move_iter().map(|part| {
+      part.unwrap()
+    }).collect();
+    match_components(components.as_slice(), parts.as_slice(), options)
+  }
+}
+
+// Split a path containing a `**` component into components.
+fn recursive_components(path: &str) -> Option<Vec<PathComponent>> {
+  let parts: Vec<&str> = path.split('/').filter(|part| {
+    part.len() > 0 && *part != "."
+  }).collect();
+
+  if !parts.iter().any(|part| *part == "**") {
+    return None
+  }
+
+  Some(parts.iter().map(|part| {
+    if *part == "**" {
+      AnyDirectories
+    } else {
+      ComponentGlob(Pattern::new(*part))
+    }
+  }).collect())
+}
+
+fn match_components(
+  components: &[PathComponent], parts: &[&str], options: MatchOptions
+) -> bool {
+  match components.head() {
+    None => parts.len() == 0,
+    Some(&AnyDirectories) => {
+      // Try consuming no directories then one more each time.
+      range(0, parts.len() + 1).any(|skip| {
+        match_components(components.tail(), parts.slice_from(skip), options)
+      })
+    },
+    Some(&ComponentGlob(ref glob)) => match parts.head() {
+      Some(part) => {
+        glob.matches_with(*part, options) &&
+          match_components(components.tail(), parts.tail(), options)
+      },
+      None => false
     }
   }
 }
@@ -53,6 +189,12 @@ impl Show for SuitePath {
 // All operations stem from the "suite" configuration.
 #[deriving(PartialEq, Show)]
 pub struct Suite<'a> {
+  /// Unique name of the suite (if it was given one).
+  pub name: Option<String>,
+
+  /// Human readable title of the suite.
+  pub title: Option<String>,
+
   /// Name of the "group" this suite belongs to.
   pub group: String,
 
@@ -62,12 +204,76 @@ pub struct Suite<'a> {
   /// A list of "paths" (may also be globs) for the suite.
   pub paths: Vec<SuitePath>,
 
+  /// Paths which are excluded from the suite. Exclusions always win over
+  /// `paths` and excluding a directory excludes everything inside of it.
+  pub exclude: Vec<SuitePath>,
+
+  /// Skip ignored files (see `ignore`) when finding the files of the suite.
+  pub respect_ignore_files: bool,
+
   /// The executable to use to run files for this suite.
-  pub executable: String
+  pub executable: String,
+
+  /// Format of the output of the executable.
+  pub format: Format,
+
+  /// The manifest file this suite was defined in.
+  pub manifest: PathWrapper,
+
+  /// Position of the suite within the suites of its manifest.
+  pub manifest_index: uint
 }
 
 
 impl<'a> Suite<'a> {
+  /// Human readable description of where the suite came from (for listings
+  /// and error messages).
+  pub fn source(&self) -> String {
+    format!(
+      "suite `{}` defined in {} (#{})",
+      self.label(),
+      self.manifest,
+      self.manifest_index
+    )
+  }
+
+  /// The name of the suite or the group for unnamed suites.
+  pub fn label(&self) -> &str {
+    match self.name {
+      Some(ref name) => name.as_slice(),
+      None => self.group.as_slice()
+    }
+  }
+
+  /// Every distinct root of the suite: the root of the suite itself followed
+  /// by the roots of any paths added by extending manifests.
+  pub fn roots(&self) -> Vec<&Path> {
+    let mut roots = vec![self.root.get()];
+    for suite_path in self.paths.iter() {
+      let root = suite_path.root.get();
+      if !roots.contains(&root) {
+        roots.push(root);
+      }
+    }
+    roots
+  }
+
+  /// The deepest root of the suite which is an ancestor of the path.
+  pub fn root_for(&self, path: &Path) -> Option<&Path> {
+    let mut closest: Option<&Path> = None;
+    for root in self.roots().move_iter() {
+      if !root.is_ancestor_of(path) {
+        continue
+      }
+
+      closest = match closest {
+        Some(current) if current >= root => Some(current),
+        _ => Some(root)
+      };
+    }
+    closest
+  }
+
   /// Determine if a given file matches any of the "path" pattern rules in this
   /// suite.
   pub fn contains_path(&self, path: &Path) -> bool {
@@ -75,40 +281,98 @@ impl<'a> Suite<'a> {
     // somewhere higher up likely...
     let match_options = MatchOptions {
       case_sensitive: true,
-      // Do not match into subdirectories implicitly
+      // Do not match into subdirectories implicitly (only `**` does)
       require_literal_separator: true,
       require_literal_leading_dot: false
     };
 
 
+    if self.is_excluded(path, match_options) {
+      return false
+    }
+
     for suite_path in self.paths.iter() {
-      let matches = suite_path.pattern.matches_path_with(path, match_options);
+      let matches = suite_path.matches(path, match_options);
       if matches {
         return true
       }
     }
     return false
   }
+
+  // A path is excluded when it (or any directory it is in below the root of
+  // the exclusion) matches one of the exclusions.
+  fn is_excluded(&self, path: &Path, options: MatchOptions) -> bool {
+    self.exclude.iter().any(|exclusion| {
+      let root = exclusion.root.get();
+      let mut current = path.clone();
+      while root.is_ancestor_of(&current) && current != *root {
+        if exclusion.matches(&current, options) {
+          return true
+        }
+        current = current.dir_path();
+      }
+      false
+    })
+  }
+}
+
+/// Find a suite by its unique name.
+pub fn suite_by_name<'a, 'b>(
+  suites: &'a Vec<Suite<'b>>, name: &str
+) -> Option<&'a Suite<'b>> {
+  suites.iter().find(|suite| {
+    match suite.name {
+      Some(ref suite_name) => suite_name.as_slice() == name,
+      None => false
+    }
+  })
+}
+
+/// Indexes of every suite (from any manifest) which belongs to the group.
+pub fn suites_in_group(suites: &Vec<Suite>, group: &str) -> Vec<uint> {
+  suites.iter().enumerate().filter(|&(_, suite)| {
+    suite.group.as_slice() == group
+  }).map(|(index, _)| index).collect()
+}
+
+/// Same as `suites_in_group` but a group without any suites is an error (for
+/// groups given by the user).
+pub fn select_group(
+  suites: &Vec<Suite>, group: &str
+) -> OverlordResult<Vec<uint>> {
+  let indexes = suites_in_group(suites, group);
+  if indexes.len() == 0 {
+    return Err(OverlordError::new(format!(
+      "Unknown group: '{}' (expected one of: {})",
+      group,
+      group_names(suites).connect(", ")
+    )));
+  }
+  Ok(indexes)
+}
+
+/// Names of all groups in the order they are first defined.
+pub fn group_names(suites: &Vec<Suite>) -> Vec<String> {
+  let mut names: Vec<String> = Vec::new();
+  for suite in suites.iter() {
+    if !names.contains(&suite.group) {
+      names.push(suite.group.clone());
+    }
+  }
+  names
 }
 
 #[cfg(test)]
 mod tests {
+  use test;
   use util::{PathWrapper};
-  use super::{SuitePath, Suite};
+  use super::{SuitePath, Suite, Format, ExitCodeFormat, TapFormat,
+              FORMAT_NAMES, suite_by_name, suites_in_group, select_group,
+              group_names};
 
   fn get_suite<'a>() -> Suite<'a> {
-    let root = PathWrapper::from_str("/foo");
-    let paths = vec![
-      SuitePath::new(&root, "*_test.txt".to_string()),
-      SuitePath::new(&root, "nested/bar/*_test.txt".to_string()),
-    ];
-
-    Suite {
-      group: "xfoo".to_string(),
-      root: root,
-      paths: paths,
-      executable: "cat".to_string()
-    }
+    test::get_suite("xfoo", "/foo", &["*_test.txt", "nested/bar/*_test.txt"])
   }
 
   #[test]
@@ -139,4 +403,157 @@ mod tests {
     assert!(suite.contains_path(&Path::new("/foo/nested/bar/1_test.txt")));
     assert!(suite.contains_path(&Path::new("/foo/nested/bar/2_test.txt")));
   }
+
+  #[test]
+  fn suite_path_recursive() {
+    let subject = SuitePath::new(
+      &PathWrapper::from_str("/foo"), "nested/**/*_test.txt".to_string()
+    );
+    let mut suite = get_suite();
+    suite.paths = vec![subject];
+
+    assert!(suite.contains_path(&Path::new("/foo/nested/1_test.txt")));
+    assert!(suite.contains_path(&Path::new("/foo/nested/a/1_test.txt")));
+    assert!(suite.contains_path(&Path::new("/foo/nested/a/b/1_test.txt")));
+    assert_eq!(suite.contains_path(&Path::new("/foo/1_test.txt")), false);
+    assert_eq!(
+      suite.contains_path(&Path::new("/foo/nested/a/1.txt")), false
+    );
+    assert_eq!(
+      suite.contains_path(&Path::new("/bar/nested/1_test.txt")), false
+    );
+  }
+
+  #[test]
+  fn suite_path_recursive_absolute() {
+    let subject = SuitePath::new(
+      &PathWrapper::from_str("/foo"), "/foo/nested/**/*.txt".to_string()
+    );
+    let mut suite = get_suite();
+    suite.paths = vec![subject];
+
+    assert!(suite.contains_path(&Path::new("/foo/nested/a.txt")));
+    assert!(suite.contains_path(&Path::new("/foo/nested/a/b/c.txt")));
+    assert_eq!(suite.contains_path(&Path::new("/foo/a.txt")), false);
+  }
+
+  #[test]
+  fn suite_path_recursive_everything() {
+    let subject =
+      SuitePath::new(&PathWrapper::from_str("/foo"), "**/*.txt".to_string());
+    assert!(subject.components.is_some());
+
+    let mut suite = get_suite();
+    suite.paths = vec![subject];
+    assert!(suite.contains_path(&Path::new("/foo/a.txt")));
+    assert!(suite.contains_path(&Path::new("/foo/a/b/c.txt")));
+  }
+
+  #[test]
+  fn suite_path_excluded() {
+    let mut suite = get_suite();
+    suite.exclude = vec![
+      SuitePath::new(&suite.root, "*_helper_test.txt".to_string()),
+      SuitePath::new(&suite.root, "nested/bar".to_string())
+    ];
+
+    assert!(suite.contains_path(&Path::new("/foo/1_test.txt")));
+    assert_eq!(
+      suite.contains_path(&Path::new("/foo/a_helper_test.txt")), false
+    );
+
+    // Excluding a directory excludes everything inside of it.
+    assert_eq!(
+      suite.contains_path(&Path::new("/foo/nested/bar/1_test.txt")), false
+    );
+  }
+
+  #[test]
+  fn format_from_str() {
+    assert_eq!(from_str::<Format>("tap"), Some(TapFormat));
+    assert_eq!(from_str::<Format>("exit_code"), Some(ExitCodeFormat));
+    assert_eq!(from_str::<Format>("TAP"), None);
+  }
+
+  #[test]
+  fn format_names_round_trip() {
+    for name in FORMAT_NAMES.iter() {
+      assert_eq!(from_str::<Format>(*name).unwrap().name(), *name);
+    }
+  }
+
+  #[test]
+  fn suite_source() {
+    let suite = get_suite();
+    assert_eq!(
+      suite.source(),
+      "suite `xfoo` defined in /foo/overlord.toml (#0)".to_string()
+    );
+  }
+
+  #[test]
+  fn find_suite_by_name() {
+    let mut named = get_suite();
+    named.name = Some("named".to_string());
+    let suites = vec![get_suite(), named];
+
+    assert_eq!(suite_by_name(&suites, "named").unwrap(), &suites[1]);
+    assert_eq!(suite_by_name(&suites, "xfoo"), None);
+  }
+
+  #[test]
+  fn suite_label() {
+    let mut suite = get_suite();
+    assert_eq!(suite.label(), "xfoo");
+    suite.name = Some("named".to_string());
+    assert_eq!(suite.label(), "named");
+  }
+
+  #[test]
+  fn find_suites_in_group() {
+    let mut other = get_suite();
+    other.group = "other".to_string();
+    let suites = vec![get_suite(), other, get_suite()];
+
+    assert_eq!(suites_in_group(&suites, "xfoo"), vec![0, 2]);
+    assert_eq!(suites_in_group(&suites, "other"), vec![1]);
+    assert_eq!(suites_in_group(&suites, "missing"), Vec::new());
+    assert_eq!(
+      group_names(&suites), vec!["xfoo".to_string(), "other".to_string()]
+    );
+  }
+
+  #[test]
+  fn select_unknown_group() {
+    let mut other = get_suite();
+    other.group = "other".to_string();
+    let suites = vec![get_suite(), other];
+
+    assert_eq!(select_group(&suites, "other").unwrap(), vec![1]);
+    let error = select_group(&suites, "missing").err().unwrap();
+    assert_eq!(
+      error.message,
+      "Unknown group: 'missing' (expected one of: xfoo, other)".to_string()
+    );
+  }
+
+  #[test]
+  fn suite_extended_roots() {
+    let mut suite = get_suite();
+    let extension = PathWrapper::from_str("/foo/nested/child");
+    suite.paths.push(SuitePath::new(&extension, "*.txt".to_string()));
+
+    let roots = suite.roots();
+    assert_eq!(roots.len(), 2);
+    assert!(roots[0] == &Path::new("/foo"));
+    assert!(roots[1] == &Path::new("/foo/nested/child"));
+
+    let nested = Path::new("/foo/nested/child/a.txt");
+    assert!(suite.contains_path(&nested));
+    assert!(suite.root_for(&nested).unwrap() == roots[1]);
+
+    let top = Path::new("/foo/a_test.txt");
+    assert!(suite.root_for(&top).unwrap() == roots[0]);
+    assert!(suite.root_for(&Path::new("/bar/a.txt")).is_none());
+  }
 }
@@ -1,15 +1,16 @@
 // This module handles loading all manifest files and converting files from the
 // manifest interchange format to the in memory strucutred format used in later
 // operations.
-use config::{Suite, SuitePath};
+use config::{Suite, SuitePath, Format, ExitCodeFormat, FORMAT_NAMES};
 use util::{PathWrapper};
-use interchange::{Manifest, ManifestSuite};
-use error::{OverlordError, OverlordResult};
+use interchange::{Manifest, ManifestSuite, ManifestExtension};
+use error::{OverlordError, OverlordResult, FileError, Utf8Error, TomlError,
+            DecodeError, ConfigError, CycleError};
 use serialize::{Decodable};
 use std::str;
 use toml;
 use std::io::fs::{File};
-use std::collections::hashmap::HashSet;
+use std::collections::hashmap::{HashMap, HashSet};
 use std::os;
 
 // In addition to the format itself _how_ the manifest is loaded matter for the
@@ -21,18 +22,20 @@ fn load_manifest(path: &Path) -> OverlordResult<Manifest> {
   let bytes = match File::open(path).read_to_end() {
     Ok(v) => v,
     Err(e) => {
-      return Err(OverlordError::new(format!(
+      return Err(OverlordError::with_kind(FileError(e.clone()), format!(
         "Failed to read file: '{}' {}",
          path.display(),
          e.desc
-      )));
+      )).in_manifest(path));
     }
   };
 
   let content = match str::from_utf8(bytes.as_slice()) {
     Some(v) => v,
     None => {
-      return Err(OverlordError::new("utf8 conversion error...".to_string()))
+      return Err(OverlordError::with_kind(Utf8Error, format!(
+        "Manifest is not valid UTF-8: '{}'", path.display()
+      )).in_manifest(path));
     }
   };
 
@@ -40,57 +43,235 @@ fn load_manifest(path: &Path) -> OverlordResult<Manifest> {
 
   let toml = match parser.parse() {
     Some(v) => v,
-    None => {
-      return Err(OverlordError::new("Could not parse toml file".to_string()))
-    }
+    None => return Err(parse_error(path, content, &parser))
   };
 
-  let mut decoder = toml::Decoder::new(toml::Table(toml));
+  let mut decoder = toml::Decoder::new(toml::Table(normalize_suites(toml)));
 
   // XXX: My intent was to terminte and return here but I ended up assigning
   // since I could not figure out how to annotate the decode result as
   // interchange::Manifest without the assignment.
   let manifest: Manifest = match Decodable::decode(&mut decoder) {
     Ok(v) => v,
-    // TODO: Expand error messages.
     Err(e) => {
-      return Err(OverlordError::new(format!(
-        "Error decoding message for '{}' {}",
-        path.display(), e
-      )))
+      let field = e.field.clone();
+      let location = match field {
+        Some(ref field) => format!(" (field `{}`)", field),
+        None => String::new()
+      };
+      return Err(OverlordError::with_kind(DecodeError(field), format!(
+        "Invalid manifest: '{}'{} {}",
+        path.display(), location, e
+      )).in_manifest(path));
     }
   };
 
   Ok(manifest)
 }
 
+// Convert the first error of the toml parser into an error pointing at the
+// offending line and column.
+fn parse_error(
+  path: &Path, content: &str, parser: &toml::Parser
+) -> OverlordError {
+  let (lo, desc) = match parser.errors.as_slice().head() {
+    Some(error) => (error.lo, error.desc.clone()),
+    None => (0, "unknown error".to_string())
+  };
+
+  // The parser reports the column in bytes but humans count characters.
+  let (line, offset) = parser.to_linecol(lo);
+  let snippet = content.lines().nth(line).unwrap_or("").to_string();
+  let column = snippet.as_slice().char_indices().take_while(|&(index, _)| {
+    index < offset
+  }).count();
+
+  // The path is shown along with the location by `human_error`.
+  OverlordError::with_kind(
+    TomlError(line, column), format!("Could not parse manifest: {}", desc)
+  ).in_manifest(path).with_snippet(snippet)
+}
+
+// Named suites (`[suites.name]`) are a table keyed by name rather than an
+// array of tables. Convert them to the array form (storing the key as the
+// `name`) so both forms decode into the same interchange structure. Decoded
+// tables do not keep the order of the file so named suites are sorted by name
+// (which then determines their `manifest_index` and the order they run in).
+fn normalize_suites(mut toml: toml::TomlTable) -> toml::TomlTable {
+  let named = match toml.pop(&"suites".to_string()) {
+    Some(toml::Table(named)) => named,
+    Some(other) => {
+      toml.insert("suites".to_string(), other);
+      return toml;
+    }
+    None => return toml
+  };
+
+  let mut named: Vec<(String, toml::Value)> = named.move_iter().collect();
+  named.sort_by(|&(ref a, _), &(ref b, _)| a.cmp(b));
+
+  let suites = named.move_iter().map(|(name, suite)| {
+    match suite {
+      toml::Table(mut table) => {
+        table.insert("name".to_string(), toml::String(name));
+        toml::Table(table)
+      },
+      // Let the decoder report the invalid value.
+      other => other
+    }
+  }).collect();
+
+  toml.insert("suites".to_string(), toml::Array(suites));
+  toml
+}
+
+// Human readable identifier for a suite which has not been converted yet.
+fn manifest_suite_label(suite: &ManifestSuite) -> String {
+  match (&suite.name, &suite.group) {
+    (&Some(ref name), _) => name.clone(),
+    (&None, &Some(ref group)) => group.clone(),
+    (&None, &None) => "<unnamed>".to_string()
+  }
+}
+
+// Formats are optional but when given they must be one we know how to parse.
+fn convert_format(
+  path: &Path, suite: &ManifestSuite
+) -> OverlordResult<Format> {
+  let name = match suite.format {
+    Some(ref v) => v,
+    None => return Ok(ExitCodeFormat)
+  };
+
+  match from_str::<Format>(name.as_slice()) {
+    Some(format) => Ok(format),
+    None => Err(OverlordError::with_kind(ConfigError, format!(
+      "Unknown format '{}' for suite '{}' in '{}' (expected one of: {})",
+      name,
+      manifest_suite_label(suite),
+      path.display(),
+      FORMAT_NAMES.connect(", ")
+    )).in_manifest(path))
+  }
+}
+
 // Convert the toml format into the in memory config format.
-fn convert_manifest_suite<'a>(path: &Path, suite: &ManifestSuite) -> Suite<'a> {
+fn convert_manifest_suite<'a>(
+  path: &Path, index: uint, suite: &ManifestSuite
+) -> OverlordResult<Suite<'a>> {
   let root = PathWrapper::new(path.dir_path());
   let paths = suite.paths.iter().map(|path| {
     SuitePath::new(&root, path.clone())
   }).collect();
 
-  Suite {
+  let exclude = match suite.exclude {
+    Some(ref exclude) => exclude.iter().map(|path| {
+      SuitePath::new(&root, path.clone())
+    }).collect(),
+    None => Vec::new()
+  };
+
+  // Named suites belong to a group of the same name unless told otherwise.
+  let group = match (&suite.group, &suite.name) {
+    (&Some(ref group), _) => group.clone(),
+    (&None, &Some(ref name)) => name.clone(),
+    (&None, &None) => {
+      return Err(OverlordError::with_kind(ConfigError, format!(
+        "Suite #{} in '{}' requires a group or a name",
+        index,
+        path.display()
+      )).in_manifest(path));
+    }
+  };
+
+  Ok(Suite {
+    name: suite.name.clone(),
+    title: suite.title.clone(),
     root: root,
-    group: suite.group.clone(),
+    group: group,
     paths: paths,
-    executable: suite.executable.clone()
+    exclude: exclude,
+    respect_ignore_files: suite.respect_ignore_files.unwrap_or(true),
+    executable: suite.executable.clone(),
+    format: try!(convert_format(path, suite)),
+    manifest: PathWrapper::new(path.clone()),
+    manifest_index: index
+  })
+}
+
+// An extension waiting for the whole tree to be loaded before it is applied to
+// the suite it extends.
+struct PendingExtension {
+  // Manifest which contains the extension.
+  manifest: Path,
+
+  // Manifests which imported the extending manifest (outermost first).
+  ancestors: Vec<Path>,
+
+  extension: ManifestExtension
+}
+
+/// Options controlling how the tree of manifests is imported.
+pub struct ImportOptions {
+  /// Fail with an error on circular imports rather than skipping the import
+  /// (with a warning) and loading everything else.
+  pub strict: bool
+}
+
+impl ImportOptions {
+  pub fn new() -> ImportOptions {
+    ImportOptions { strict: false }
   }
 }
 
+// State shared by every manifest in a single (recursive) import.
+struct ImportState<'a> {
+  options: &'a ImportOptions,
+
+  // Every manifest which has been loaded. A manifest imported by more than
+  // one manifest (a "diamond") is only loaded the first time.
+  loaded: HashSet<Path>,
+
+  // Manifests which are currently being imported (outermost first). Seeing
+  // one of these again means the imports are circular.
+  stack: Vec<Path>,
+
+  extensions: Vec<PendingExtension>,
+
+  warnings: Vec<OverlordError>
+}
+
+// Describe the import chain of a circular import which ends with `path`.
+fn import_cycle(stack: &Vec<Path>, path: &Path) -> OverlordError {
+  let start = stack.iter().position(|entry| entry == path).unwrap_or(0);
+  let mut chain: Vec<String> = stack.slice_from(start).iter().map(|entry| {
+    format!("{}", entry.display())
+  }).collect();
+  chain.push(format!("{}", path.display()));
+
+  let message = format!("Circular manifest import: {}", chain.connect(" -> "));
+  OverlordError::with_kind(CycleError(chain), message).in_manifest(path)
+}
+
 fn issue_import(
-  path: Path, seen_paths: &mut HashSet<Path>
+  path: Path, state: &mut ImportState
 ) -> OverlordResult<Vec<Suite>> {
 
   // Check for circular dependencies.
-  if seen_paths.contains(&path) {
-    // TODO: Reconsider returning an error on seen paths.
+  if state.stack.contains(&path) {
+    let error = import_cycle(&state.stack, &path);
+    if state.options.strict {
+      return Err(error);
+    }
+    state.warnings.push(error);
     return Ok(Vec::new());
   }
 
   // Ensure we don't load the same manifest twice.
-  seen_paths.insert(path.clone());
+  if state.loaded.contains(&path) {
+    return Ok(Vec::new());
+  }
+  state.loaded.insert(path.clone());
 
   // Attempt to load the module for the seen path.
   let manifest = try!(load_manifest(&path));
@@ -98,8 +279,19 @@ fn issue_import(
 
   // Not all manifests have suites so handle both cases.
   if manifest.suites.is_some() {
-    for suite in manifest.suites.unwrap().iter() {
-      suites.push(convert_manifest_suite(&path, suite));
+    for (index, suite) in manifest.suites.unwrap().iter().enumerate() {
+      suites.push(try!(convert_manifest_suite(&path, index, suite)));
+    }
+  }
+
+  // Extensions can only be applied once the suites they extend are loaded.
+  if manifest.extends.is_some() {
+    for extension in manifest.extends.unwrap().move_iter() {
+      state.extensions.push(PendingExtension {
+        manifest: path.clone(),
+        ancestors: state.stack.clone(),
+        extension: extension
+      });
     }
   }
 
@@ -108,31 +300,131 @@ fn issue_import(
   if manifest.manifests.is_none() {
     Ok(suites)
   } else {
+    state.stack.push(path.clone());
     for sub_manifest in manifest.manifests.unwrap().iter() {
       let sub_manifest_path = Path::new(sub_manifest.as_slice());
       let absolute_manifest_path = path.dir_path().join(sub_manifest_path);
-      let sub_suites = try!(issue_import(absolute_manifest_path, seen_paths));
+      let sub_suites = try!(issue_import(absolute_manifest_path, state));
       suites.push_all_move(sub_suites);
     }
+    state.stack.pop();
     Ok(suites)
   }
 }
 
+// Add the paths of an extension to the named suite it extends. Paths are
+// resolved relative to the extending manifest while everything else is
+// inherited from the suite.
+fn apply_extension(
+  suites: &mut Vec<Suite>, pending: &PendingExtension
+) -> OverlordResult<()> {
+  let name = pending.extension.suite.as_slice();
+  let suite = suites.mut_iter().find(|suite| {
+    match suite.name {
+      Some(ref suite_name) => suite_name.as_slice() == name,
+      None => false
+    }
+  });
+
+  let suite = match suite {
+    Some(suite) => suite,
+    None => {
+      return Err(OverlordError::with_kind(ConfigError, format!(
+        "Unknown suite '{}' extended in '{}'",
+        name,
+        pending.manifest.display()
+      )).in_manifest(&pending.manifest));
+    }
+  };
+
+  if !pending.ancestors.contains(suite.manifest.get()) {
+    return Err(OverlordError::with_kind(ConfigError, format!(
+      "Suite '{}' extended in '{}' is not defined by an ancestor manifest \
+       ({})",
+      name,
+      pending.manifest.display(),
+      suite.source()
+    )).in_manifest(&pending.manifest));
+  }
+
+  let root = PathWrapper::new(pending.manifest.dir_path());
+  for path in pending.extension.paths.iter() {
+    suite.paths.push(SuitePath::new(&root, path.clone()));
+  }
+  Ok(())
+}
+
+/// Import every suite from the manifest and (recursively) the manifests it
+/// references. Circular imports are skipped with a logged warning.
 pub fn import<'a>(path: Path) -> OverlordResult<Vec<Suite<'a>>> {
+  import_with(path, &ImportOptions::new(), |warning| {
+    warn!("{}", warning.message);
+  })
+}
+
+/// Import with the given options calling `on_warning` for every problem which
+/// did not prevent the import.
+pub fn import_with<'a>(
+  path: Path, options: &ImportOptions, on_warning: |&OverlordError|
+) -> OverlordResult<Vec<Suite<'a>>> {
   let normalized_path = os::make_absolute(&path);
   // Phase 1 is to load _all_ manifests recursively. (without loading the same
   // file twice!)
-  let mut seen_paths = HashSet::new();
+  let mut state = ImportState {
+    options: options,
+    loaded: HashSet::new(),
+    stack: Vec::new(),
+    extensions: Vec::new(),
+    warnings: Vec::new()
+  };
 
   // Always insert the root to avoid tricky stuff...
-  issue_import(normalized_path, &mut seen_paths)
+  let mut suites = try!(issue_import(normalized_path, &mut state));
+  try!(check_unique_names(&suites));
+
+  // Phase 2 is to apply extensions now that every suite is known.
+  for pending in state.extensions.iter() {
+    try!(apply_extension(&mut suites, pending));
+  }
+
+  for warning in state.warnings.iter() {
+    on_warning(warning);
+  }
+  Ok(suites)
+}
+
+// Suite names must be unique across every imported manifest.
+fn check_unique_names(suites: &Vec<Suite>) -> OverlordResult<()> {
+  let mut names: HashMap<&str, &Suite> = HashMap::new();
+  for suite in suites.iter() {
+    let name = match suite.name {
+      Some(ref name) => name.as_slice(),
+      None => continue
+    };
+
+    match names.find(&name) {
+      Some(other) => {
+        return Err(OverlordError::with_kind(ConfigError, format!(
+          "Duplicate suite name '{}' defined in {} and {}",
+          name,
+          other.manifest,
+          suite.manifest
+        )).in_manifest(suite.manifest.get()));
+      },
+      None => {}
+    }
+    names.insert(name, suite);
+  }
+  Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-  use config::{SuitePath};
+  use config::{SuitePath, ExitCodeFormat, TapFormat, suite_by_name};
   use test::{assert_path_wrapper_eq};
-  use config_loader::{import};
+  use config_loader::{import, import_with, ImportOptions};
+  use error::{FileError, TomlError, DecodeError, CycleError};
+  use std::os;
 
   #[test]
   fn load_simple_manifest() {
@@ -150,6 +442,121 @@ mod tests {
 
     assert_eq!(suite.executable, "cat".to_string());
     assert_eq!(suite.group, "unit".to_string());
+    assert_eq!(suite.format, ExitCodeFormat);
+  }
+
+  #[test]
+  fn load_invalid_toml() {
+    let result = import(Path::new("test/errors/toml/overlord.toml"));
+    let error = result.err().unwrap();
+
+    // The third line is missing a value.
+    match error.kind {
+      TomlError(line, column) => assert_eq!((line, column), (2, 8)),
+      _ => fail!("Expected a toml error got {}", error.kind)
+    }
+    assert_eq!(error.snippet, Some("paths = @".to_string()));
+    assert!(error.message.as_slice().starts_with("Could not parse manifest: "));
+    assert_path_wrapper_eq(
+      &Path::new("test/errors/toml/overlord.toml"),
+      error.manifest.as_ref().unwrap()
+    );
+  }
+
+  #[test]
+  fn load_invalid_manifest() {
+    let result = import(Path::new("test/errors/decode/overlord.toml"));
+    let error = result.err().unwrap();
+    match error.kind {
+      DecodeError(_) => {},
+      _ => fail!("Expected a decode error got {}", error.kind)
+    }
+  }
+
+  #[test]
+  fn load_missing_manifest() {
+    let result = import(Path::new("test/errors/missing/overlord.toml"));
+    match result.err().unwrap().kind {
+      FileError(_) => {},
+      kind => fail!("Expected a file error got {}", kind)
+    }
+  }
+
+  #[test]
+  fn load_suite_format() {
+    let suites = import(Path::new("test/formats/overlord.toml")).unwrap();
+    assert_eq!(suites[0].format, TapFormat);
+  }
+
+  #[test]
+  fn load_unknown_format() {
+    let result = import(Path::new("test/formats/invalid/overlord.toml"));
+    let error = result.err().unwrap();
+    assert!(error.message.as_slice().contains("Unknown format 'xml'"));
+  }
+
+  #[test]
+  fn load_named_suites() {
+    let suites = import(Path::new("test/globs/overlord.toml")).unwrap();
+    assert_eq!(suites.len(), 1);
+    assert_eq!(suites[0].name, Some("globs".to_string()));
+    assert_eq!(suites[0].title, Some("Globs win".to_string()));
+    assert_eq!(suites[0].group, "globs".to_string());
+
+    let suite = suite_by_name(&suites, "globs").unwrap();
+    assert_path_wrapper_eq(&Path::new("test/globs"), &suite.root);
+  }
+
+  #[test]
+  fn load_named_suites_in_name_order() {
+    let suites = import(Path::new("test/named/overlord.toml")).unwrap();
+    let names: Vec<Option<String>> = suites.iter().map(|suite| {
+      suite.name.clone()
+    }).collect();
+
+    assert_eq!(names, vec![
+      Some("alpha".to_string()),
+      Some("mid".to_string()),
+      Some("zeta".to_string())
+    ]);
+    for (index, suite) in suites.iter().enumerate() {
+      assert_eq!(suite.manifest_index, index);
+    }
+  }
+
+  #[test]
+  fn load_duplicate_names() {
+    let result = import(Path::new("test/duplicate/overlord.toml"));
+    let error = result.err().unwrap();
+    assert!(
+      error.message.as_slice().contains("Duplicate suite name 'unit'")
+    );
+  }
+
+  #[test]
+  fn load_extended_suite() {
+    let suites = import(Path::new("test/extend/overlord.toml")).unwrap();
+    assert_eq!(suites.len(), 1);
+
+    let ref suite = suites[0];
+    assert_eq!(suite.paths.len(), 2);
+    assert_path_wrapper_eq(&Path::new("test/extend"), &suite.paths[0].root);
+    assert_path_wrapper_eq(
+      &Path::new("test/extend/child"), &suite.paths[1].root
+    );
+    assert!(suite.contains_path(
+      &os::make_absolute(&Path::new("test/extend/child/b.txt"))
+    ));
+  }
+
+  #[test]
+  fn load_extension_of_non_ancestor() {
+    let result = import(Path::new("test/extend/unrelated/overlord.toml"));
+    let error = result.err().unwrap();
+    assert!(error.message.as_slice().contains(
+      "Suite 'sibling' extended in"
+    ));
+    assert!(error.message.as_slice().contains("not defined by an ancestor"));
   }
 
   #[test]
@@ -169,6 +576,13 @@ mod tests {
     assert_path_wrapper_eq(
       &Path::new("test/multimanifest/nested/another"), &suites[3].root
     );
+
+    // Every suite knows which manifest it came from.
+    assert_path_wrapper_eq(
+      &Path::new("test/multimanifest/nested/overlord.toml"),
+      &suites[2].manifest
+    );
+    assert_eq!(suites[2].manifest_index, 0);
   }
 
   #[test]
@@ -178,4 +592,51 @@ mod tests {
     assert_path_wrapper_eq(&Path::new("test/circular"), &suites[0].root);
     assert_eq!(suites.len(), 1);
   }
+
+  #[test]
+  fn circular_references_warn() {
+    let mut warnings = Vec::new();
+    let path = Path::new("test/cycle/overlord.toml");
+    let suites = import_with(path, &ImportOptions::new(), |warning| {
+      warnings.push(warning.message.clone());
+    }).unwrap();
+
+    assert_eq!(suites.len(), 1);
+    assert_eq!(warnings.len(), 1);
+
+    // The full chain is reported starting (and ending) with the manifest
+    // which was imported again.
+    let root = os::make_absolute(&Path::new("test/cycle"));
+    assert_eq!(warnings[0], format!(
+      "Circular manifest import: {} -> {} -> {}",
+      root.join("overlord.toml").display(),
+      root.join("child/overlord.toml").display(),
+      root.join("overlord.toml").display()
+    ));
+  }
+
+  #[test]
+  fn circular_references_strict() {
+    let options = ImportOptions { strict: true };
+    let path = Path::new("test/circular/overlord.toml");
+    let error = import_with(path, &options, |_| {}).err().unwrap();
+    assert!(error.message.as_slice().starts_with("Circular manifest import"));
+    match error.kind {
+      CycleError(ref chain) => assert_eq!(chain.len(), 2),
+      _ => fail!("Expected a cycle error got {}", error.kind)
+    }
+  }
+
+  #[test]
+  fn diamond_references() {
+    // Both sides import the same manifest which is not a cycle.
+    let mut warnings = 0u;
+    let options = ImportOptions { strict: true };
+    let path = Path::new("test/diamond/overlord.toml");
+    let suites = import_with(path, &options, |_| warnings += 1).unwrap();
+
+    assert_eq!(warnings, 0);
+    assert_eq!(suites.len(), 1);
+    assert_path_wrapper_eq(&Path::new("test/diamond/shared"), &suites[0].root);
+  }
 }
@@ -1 +1,6 @@
 pub static MANIFEST: &'static str = "overlord.toml";
+
+// Files listing paths to skip when finding the files of a suite (later files
+// take precedence).
+pub static IGNORE_FILES: &'static [&'static str] =
+  &[".gitignore", ".ignore", ".overlordignore"];
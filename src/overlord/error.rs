@@ -1,17 +1,197 @@
-#[deriving(Show)]
+// Errors from every part of overlord. Each error has a kind (so callers can
+// tell errors apart without matching on messages), a human readable message
+// and optionally the manifest it relates to and the error which caused it.
+use util::{PathWrapper};
+use std::io;
+use std::iter::{Repeat};
+
+#[deriving(Clone, PartialEq, Show)]
+pub enum ErrorKind {
+  /// Any error without a more specific kind.
+  GenericError,
+
+  /// Reading a file or directory failed.
+  FileError(io::IoError),
+
+  /// A manifest is not valid UTF-8.
+  Utf8Error,
+
+  /// A manifest is not valid TOML. Contains the (zero based) line and column
+  /// (in characters) of the error.
+  TomlError(uint, uint),
+
+  /// A manifest is valid TOML but not a valid manifest. Contains the path of
+  /// the offending field (if known).
+  DecodeError(Option<String>),
+
+  /// A manifest is valid but its contents are not (unknown formats, duplicate
+  /// suite names, etc...).
+  ConfigError,
+
+  /// The executable of a suite could not be run. Contains the executable.
+  ExecutableError(String),
+
+  /// Manifests import each other. Contains every manifest in the cycle (the
+  /// first and last are the same).
+  CycleError(Vec<String>)
+}
+
+#[deriving(Clone, Show)]
 pub struct OverlordError {
-  pub message: String
+  pub kind: ErrorKind,
+
+  pub message: String,
+
+  /// Manifest the error relates to.
+  pub manifest: Option<PathWrapper>,
+
+  /// The line of the manifest the error was found in (for TOML errors).
+  pub snippet: Option<String>,
+
+  /// The error which caused this one.
+  pub cause: Option<Box<OverlordError>>
 }
 
 impl OverlordError {
+  /// Render the error (and every cause) for humans. TOML errors include the
+  /// location and offending line with a caret under the exact column.
   pub fn human_error(self) -> String {
-    // TODO: Make this look amazing.
-    self.message
+    let mut output = self.message.clone();
+
+    match (&self.kind, &self.manifest) {
+      (&TomlError(line, column), &Some(ref manifest)) => {
+        output.push_str(format!(
+          "\n  --> {}:{}:{}", manifest, line + 1, column + 1
+        ).as_slice());
+
+        match self.snippet {
+          Some(ref snippet) => {
+            output.push_str(caret_snippet(
+              snippet.as_slice(), line, column
+            ).as_slice());
+          },
+          None => {}
+        }
+      },
+      _ => {}
+    }
+
+    match self.cause {
+      Some(cause) => {
+        output.push_str("\nCaused by: ");
+        output.push_str((*cause).human_error().as_slice());
+      },
+      None => {}
+    }
+    output
   }
 
   pub fn new(message: String) -> OverlordError {
-    OverlordError{message: message}
+    OverlordError::with_kind(GenericError, message)
+  }
+
+  pub fn with_kind(kind: ErrorKind, message: String) -> OverlordError {
+    OverlordError {
+      kind: kind,
+      message: message,
+      manifest: None,
+      snippet: None,
+      cause: None
+    }
+  }
+
+  /// Associate the error with the manifest it was found in.
+  pub fn in_manifest(mut self, manifest: &Path) -> OverlordError {
+    self.manifest = Some(PathWrapper::new(manifest.clone()));
+    self
+  }
+
+  /// Attach the offending line of the manifest.
+  pub fn with_snippet(mut self, snippet: String) -> OverlordError {
+    self.snippet = Some(snippet);
+    self
+  }
+
+  /// Chain the error which caused this one.
+  pub fn caused_by(mut self, cause: OverlordError) -> OverlordError {
+    self.cause = Some(box cause);
+    self
   }
 }
 
+// Show a single line with its (one based) number and a caret pointing at the
+// column. Tabs before the column are kept so the caret lines up however wide
+// the terminal renders them.
+fn caret_snippet(snippet: &str, line: uint, column: uint) -> String {
+  let number = format!("{}", line + 1);
+  let gutter = String::from_char(number.len(), ' ');
+  let chars = snippet.chars().chain(Repeat::new(' '));
+  let padding: String = chars.take(column).map(|c| {
+    if c == '\t' { '\t' } else { ' ' }
+  }).collect();
+  format!(
+    "\n {} |\n {} | {}\n {} | {}^",
+    gutter, number, snippet, gutter, padding
+  )
+}
+
 pub type OverlordResult<T> = Result<T, OverlordError>;
+
+#[cfg(test)]
+mod tests {
+  use super::{OverlordError, GenericError, TomlError, ConfigError};
+
+  #[test]
+  fn new_generic_error() {
+    let error = OverlordError::new("nope".to_string());
+    assert_eq!(error.kind, GenericError);
+    assert_eq!(error.clone().human_error(), "nope".to_string());
+  }
+
+  #[test]
+  fn human_error_with_snippet() {
+    let error = OverlordError::with_kind(
+      TomlError(2, 8), "Could not parse manifest: expected a value".to_string()
+    ).in_manifest(&Path::new("/foo/overlord.toml"))
+     .with_snippet("paths = [".to_string());
+
+    let expected = vec![
+      "Could not parse manifest: expected a value",
+      "  --> /foo/overlord.toml:3:9",
+      "   |",
+      " 3 | paths = [",
+      "   |         ^"
+    ].connect("\n");
+    assert_eq!(error.human_error(), expected);
+  }
+
+  #[test]
+  fn human_error_caret_after_tabs_and_unicode() {
+    let error = OverlordError::with_kind(
+      TomlError(0, 5), "Could not parse manifest: expected a value".to_string()
+    ).in_manifest(&Path::new("/foo/overlord.toml"))
+     .with_snippet("\t\u00e9 = @".to_string());
+
+    let expected = vec![
+      "Could not parse manifest: expected a value",
+      "  --> /foo/overlord.toml:1:6",
+      "   |",
+      " 1 | \t\u00e9 = @",
+      "   | \t    ^"
+    ].connect("\n");
+    assert_eq!(error.human_error(), expected);
+  }
+
+  #[test]
+  fn human_error_with_cause() {
+    let cause = OverlordError::new("file not found".to_string());
+    let error = OverlordError::with_kind(
+      ConfigError, "Failed to load manifest".to_string()
+    ).caused_by(cause);
+
+    assert_eq!(
+      error.human_error(),
+      "Failed to load manifest\nCaused by: file not found".to_string()
+    );
+  }
+}
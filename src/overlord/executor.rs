@@ -0,0 +1,372 @@
+// Execution of individual files within a suite. Each file is run by spawning
+// the suite's executable with the file appended as the final argument.
+use config::{Suite, Format};
+use error::{OverlordError, OverlordResult, ExecutableError, FileError};
+use parser::{for_format};
+use results::{TestResult, Pass, Fail};
+use std::io::{BufferedReader};
+use std::io::process::{Command, ProcessExit, ExitStatus};
+use std::time::{Duration};
+use time;
+
+/// The outcome of running a single file with a suite's executable.
+#[deriving(Clone)]
+pub struct FileResult {
+  /// File which was passed to the executable.
+  pub path: Path,
+
+  /// Exit status (or signal) of the process.
+  pub status: ProcessExit,
+
+  /// Captured stdout of the process (lossy utf8).
+  pub stdout: String,
+
+  /// Captured stderr of the process (lossy utf8).
+  pub stderr: String,
+
+  /// Wall clock time spent running the process.
+  pub duration: Duration,
+
+  /// Individual tests parsed from the output (empty if the output is not
+  /// parsed).
+  pub tests: Vec<TestResult>
+}
+
+impl FileResult {
+  /// A failing result for a file which could not be run at all (missing
+  /// executable, etc...) so it can be reported alongside the other files.
+  pub fn from_error(path: &Path, error: OverlordError) -> FileResult {
+    let mut test = TestResult::new(format!("{}", path.display()), Fail);
+    test.message = Some(error.message.clone());
+
+    FileResult {
+      path: path.clone(),
+      status: ExitStatus(-1),
+      stdout: String::new(),
+      stderr: error.human_error(),
+      duration: Duration::zero(),
+      tests: vec![test]
+    }
+  }
+
+  pub fn success(&self) -> bool {
+    self.status.success()
+  }
+
+  /// The tests for this file for reporting purposes. When no individual tests
+  /// were parsed the file itself is treated as a single test which passes
+  /// based on the exit status. A failing exit status always results in at
+  /// least one failing test.
+  pub fn test_results(&self) -> Vec<TestResult> {
+    let mut tests = self.tests.clone();
+
+    if tests.len() == 0 {
+      let name = format!("{}", self.path.display());
+      let mut test =
+        TestResult::new(name, if self.success() { Pass } else { Fail });
+      test.duration = Some(self.duration);
+      if !self.success() {
+        test.message = Some(format!("Exited with {}", self.status));
+      }
+      tests.push(test);
+    } else if !self.success() && !tests.iter().any(|test| test.failed()) {
+      let mut test = TestResult::new(format!("{}", self.path.display()), Fail);
+      test.message = Some(format!("Exited with {}", self.status));
+      tests.push(test);
+    }
+
+    tests
+  }
+}
+
+/// Split an executable string (`test -x`) into the program (`test`) and the
+/// arguments (`["-x"]`) which are passed before the file.
+pub fn split_executable(
+  executable: &str
+) -> OverlordResult<(String, Vec<String>)> {
+  let mut parts = executable.words().map(|part| part.to_string());
+
+  match parts.next() {
+    Some(program) => Ok((program, parts.collect())),
+    None => Err(OverlordError::with_kind(
+      ExecutableError(executable.to_string()),
+      format!("Invalid executable: '{}'", executable)
+    ))
+  }
+}
+
+/// Run a single file with the given executable from the `cwd` directory and
+/// wait for it to complete. The output is parsed into individual tests
+/// according to `format`.
+pub fn execute(
+  executable: &str, format: Format, cwd: &Path, file: &Path
+) -> OverlordResult<FileResult> {
+  execute_streaming(executable, format, cwd, file, |_| {})
+}
+
+/// Same as `execute` but `on_test` is called with each test as soon as it is
+/// parsed from the output (while the process is still running).
+pub fn execute_streaming(
+  executable: &str,
+  format: Format,
+  cwd: &Path,
+  file: &Path,
+  on_test: |&TestResult|
+) -> OverlordResult<FileResult> {
+  let (program, args) = try!(split_executable(executable));
+
+  let mut command = Command::new(program.as_slice());
+  command.args(args.as_slice()).arg(file).cwd(cwd);
+
+  let start = time::precise_time_ns();
+  let mut process = match command.spawn() {
+    Ok(v) => v,
+    Err(e) => {
+      let cause = OverlordError::with_kind(FileError(e.clone()), e.to_string());
+      return Err(OverlordError::with_kind(
+        ExecutableError(executable.to_string()),
+        format!("Failed to run '{}' for '{}'", executable, file.display())
+      ).caused_by(cause));
+    }
+  };
+
+  // Stderr is read on its own task so a full stderr pipe can never block the
+  // process while we are reading stdout.
+  let stderr_pipe = process.stderr.take();
+  let (stderr_tx, stderr_rx) = channel();
+  spawn(proc() {
+    let bytes = match stderr_pipe {
+      Some(mut pipe) => pipe.read_to_end().unwrap_or(Vec::new()),
+      None => Vec::new()
+    };
+    stderr_tx.send(bytes);
+  });
+
+  let mut parser = for_format(format);
+  let mut stdout = String::new();
+  let mut tests = Vec::new();
+
+  match process.stdout.take() {
+    Some(pipe) => {
+      let mut reader = BufferedReader::new(pipe);
+      loop {
+        let bytes = match reader.read_until(b'\n') {
+          Ok(v) => v,
+          Err(_) => break
+        };
+
+        let line = String::from_utf8_lossy(bytes.as_slice()).into_string();
+        stdout.push_str(line.as_slice());
+
+        match parser {
+          Some(ref mut parser) => {
+            let content = line.as_slice().trim_right_chars(|c: char| {
+              c == '\n' || c == '\r'
+            });
+            for test in parser.feed(content).move_iter() {
+              on_test(&test);
+              tests.push(test);
+            }
+          },
+          None => {}
+        }
+      }
+    },
+    None => {}
+  }
+
+  match parser {
+    Some(ref mut parser) => {
+      for test in parser.finish().move_iter() {
+        on_test(&test);
+        tests.push(test);
+      }
+    },
+    None => {}
+  }
+
+  let status = match process.wait() {
+    Ok(v) => v,
+    Err(e) => {
+      return Err(OverlordError::new(format!(
+        "Failed waiting for '{}' for '{}' {}",
+        executable,
+        file.display(),
+        e.desc
+      )));
+    }
+  };
+  let elapsed = time::precise_time_ns() - start;
+  let stderr = stderr_rx.recv();
+
+  Ok(FileResult {
+    path: file.clone(),
+    status: status,
+    stdout: stdout,
+    stderr: String::from_utf8_lossy(stderr.as_slice()).into_string(),
+    duration: Duration::nanoseconds(elapsed as i64),
+    tests: tests
+  })
+}
+
+/// Run a single file using the executable of the given suite. The process is
+/// run from the root of the suite.
+pub fn run_file(suite: &Suite, file: &Path) -> OverlordResult<FileResult> {
+  execute(suite.executable.as_slice(), suite.format, suite.root.get(), file)
+}
+
+#[cfg(test)]
+mod tests {
+  use config::{ExitCodeFormat, TapFormat};
+  use config_loader::{import};
+  use results::{TestResult, Pass, Fail, Skip};
+  use std::io::process::{ExitStatus};
+  use std::time::{Duration};
+  use std::os;
+  use error::{OverlordError, ExecutableError};
+  use super::{FileResult, split_executable, execute, execute_streaming,
+              run_file};
+
+  fn file_result(code: int, tests: Vec<TestResult>) -> FileResult {
+    FileResult {
+      path: Path::new("/foo/a.txt"),
+      status: ExitStatus(code),
+      stdout: String::new(),
+      stderr: String::new(),
+      duration: Duration::milliseconds(5),
+      tests: tests
+    }
+  }
+
+  #[test]
+  fn file_result_from_error() {
+    let error = OverlordError::new("no such program".to_string());
+    let result = FileResult::from_error(&Path::new("/foo/a.txt"), error);
+
+    assert!(!result.success());
+    assert_eq!(result.stderr, "no such program".to_string());
+    let tests = result.test_results();
+    assert_eq!(tests.len(), 1);
+    assert_eq!(tests[0].status, Fail);
+    assert_eq!(tests[0].message, Some("no such program".to_string()));
+  }
+
+  #[test]
+  fn split_executable_program_only() {
+    let (program, args) = split_executable("cat").unwrap();
+    assert_eq!(program, "cat".to_string());
+    assert_eq!(args.len(), 0);
+  }
+
+  #[test]
+  fn split_executable_with_args() {
+    let (program, args) = split_executable("test  -x").unwrap();
+    assert_eq!(program, "test".to_string());
+    assert_eq!(args, vec!["-x".to_string()]);
+  }
+
+  #[test]
+  fn split_executable_empty() {
+    assert!(split_executable("   ").is_err());
+  }
+
+  #[test]
+  fn run_file_captures_stdout() {
+    let suites = import(Path::new("test/executor/overlord.toml")).unwrap();
+    let file = os::make_absolute(&Path::new("test/executor/hello.txt"));
+    let result = run_file(&suites[0], &file).unwrap();
+
+    assert!(result.success());
+    assert!(result.path == file);
+    assert_eq!(result.stdout, "hello\n".to_string());
+    assert_eq!(result.stderr, "".to_string());
+    assert_eq!(result.tests.len(), 0);
+  }
+
+  #[test]
+  fn run_file_parses_output() {
+    let suites = import(Path::new("test/formats/overlord.toml")).unwrap();
+    let file = os::make_absolute(&Path::new("test/formats/a.tap"));
+    let result = run_file(&suites[0], &file).unwrap();
+
+    assert!(result.success());
+    assert_eq!(result.tests.len(), 3);
+    assert_eq!(result.tests[0].status, Pass);
+    assert_eq!(result.tests[1].status, Fail);
+    assert_eq!(result.tests[2].status, Skip);
+  }
+
+  #[test]
+  fn execute_streaming_tests() {
+    let cwd = os::make_absolute(&Path::new("test/formats"));
+    let mut streamed = Vec::new();
+    let result = execute_streaming(
+      "cat", TapFormat, &cwd, &Path::new("a.tap"), |test| {
+        streamed.push(test.clone());
+      }
+    ).unwrap();
+
+    assert_eq!(streamed.len(), 3);
+    assert_eq!(streamed, result.tests);
+  }
+
+  #[test]
+  fn execute_with_args_exit_status() {
+    let cwd = os::make_absolute(&Path::new("test/executor"));
+    // The fixture is not executable so `test -x` exits with 1.
+    let file = Path::new("hello.txt");
+    let result = execute("test -x", ExitCodeFormat, &cwd, &file).unwrap();
+
+    assert!(!result.success());
+    assert_eq!(result.status, ExitStatus(1));
+  }
+
+  #[test]
+  fn execute_captures_stderr() {
+    let cwd = os::make_absolute(&Path::new("test/executor"));
+    let file = Path::new("missing.txt");
+    let result = execute("cat", ExitCodeFormat, &cwd, &file).unwrap();
+
+    assert!(!result.success());
+    assert!(result.stderr.len() > 0);
+  }
+
+  #[test]
+  fn execute_missing_program() {
+    let cwd = os::make_absolute(&Path::new("test/executor"));
+    let result = execute(
+      "overlord-missing-program", ExitCodeFormat, &cwd, &Path::new("hello.txt")
+    );
+    match result.err().unwrap().kind {
+      ExecutableError(ref executable) => {
+        assert_eq!(executable.as_slice(), "overlord-missing-program");
+      },
+      kind => fail!("Expected an executable error got {}", kind)
+    }
+  }
+
+  #[test]
+  fn test_results_from_exit_status() {
+    let passed = file_result(0, Vec::new()).test_results();
+    assert_eq!(passed.len(), 1);
+    assert_eq!(passed[0].name, "/foo/a.txt".to_string());
+    assert_eq!(passed[0].status, Pass);
+    assert_eq!(passed[0].duration, Some(Duration::milliseconds(5)));
+
+    let failed = file_result(2, Vec::new()).test_results();
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].status, Fail);
+    assert!(failed[0].message.is_some());
+  }
+
+  #[test]
+  fn test_results_parsed() {
+    let tests = vec![TestResult::new("one".to_string(), Pass)];
+    assert_eq!(file_result(0, tests.clone()).test_results(), tests);
+
+    // The exit status still counts when every parsed test passed.
+    let failed = file_result(1, tests).test_results();
+    assert_eq!(failed.len(), 2);
+    assert_eq!(failed[1].status, Fail);
+  }
+}
@@ -0,0 +1,133 @@
+// The reverse of path identification: given a suite find every file on disk
+// which it would run.
+use config::{Suite};
+use error::{OverlordError, OverlordResult, FileError};
+use ignore::{walk_files};
+
+/// Walk the roots of the suite and return every file which matches one of the
+/// suite's path patterns (skipping ignored files unless the suite opts out).
+/// Results are sorted so the output is stable across runs (and platforms).
+pub fn find_files(suite: &Suite) -> OverlordResult<Vec<Path>> {
+  let roots = suite.roots();
+  let mut files: Vec<Path> = Vec::new();
+
+  for root in roots.iter() {
+    // Roots nested inside of another root are covered by the outer walk.
+    let nested = roots.iter().any(|other| {
+      other != root && other.is_ancestor_of(*root)
+    });
+    if nested {
+      continue
+    }
+
+    let walked = match walk_files(*root, suite.respect_ignore_files) {
+      Ok(v) => v,
+      Err(e) => {
+        return Err(OverlordError::with_kind(FileError(e.clone()), format!(
+          "Failed to read directory: '{}' for {} {}",
+          root.display(),
+          suite.source(),
+          e.desc
+        )).in_manifest(suite.manifest.get()));
+      }
+    };
+
+    files.extend(walked.move_iter().filter(|path| {
+      suite.contains_path(path)
+    }));
+  }
+
+  files.sort_by(|a, b| a.as_vec().cmp(&b.as_vec()));
+  Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+  use config::{Suite};
+  use config_loader::{import};
+  use test::{assert_path_eq, get_suite};
+  use std::os;
+  use super::{find_files};
+
+  fn globs_suite(path: &str) -> Suite {
+    let root = os::make_absolute(&Path::new("test/globs"));
+    get_suite("globs", root.as_str().unwrap(), &[path])
+  }
+
+  #[test]
+  fn find_simple_files() {
+    let suites = import(Path::new("test/simple/overlord.toml")).unwrap();
+    let files = find_files(&suites[0]).unwrap();
+
+    assert_eq!(files.len(), 2);
+    assert_path_eq(&Path::new("test/simple/files/a.txt"), &files[0]);
+    assert_path_eq(&Path::new("test/simple/files/b.txt"), &files[1]);
+  }
+
+  #[test]
+  fn find_files_excluded() {
+    let suites = import(Path::new("test/exclude/overlord.toml")).unwrap();
+    let files = find_files(&suites[0]).unwrap();
+
+    assert_eq!(files.len(), 1);
+    assert_path_eq(&Path::new("test/exclude/a.txt"), &files[0]);
+  }
+
+  #[test]
+  fn find_files_ignored() {
+    let suites = import(Path::new("test/ignore/overlord.toml")).unwrap();
+
+    // build/ is in .gitignore, *.tmp.txt in .ignore and .overlordignore
+    // includes keep.tmp.txt again.
+    let files = find_files(&suites[0]).unwrap();
+    assert_eq!(files.len(), 2);
+    assert_path_eq(&Path::new("test/ignore/a.txt"), &files[0]);
+    assert_path_eq(&Path::new("test/ignore/keep.tmp.txt"), &files[1]);
+
+    // The second suite does not respect ignore files.
+    let files = find_files(&suites[1]).unwrap();
+    assert_eq!(files.len(), 4);
+    assert_path_eq(&Path::new("test/ignore/build/b.txt"), &files[1]);
+  }
+
+  #[test]
+  fn find_extended_files() {
+    let suites = import(Path::new("test/extend/overlord.toml")).unwrap();
+    let files = find_files(&suites[0]).unwrap();
+
+    assert_eq!(files.len(), 2);
+    assert_path_eq(&Path::new("test/extend/a.txt"), &files[0]);
+    assert_path_eq(&Path::new("test/extend/child/b.txt"), &files[1]);
+  }
+
+  #[test]
+  fn find_files_single_level() {
+    let suite = globs_suite("folder/*.txt");
+
+    // Note that folder/nested/forgotten.txt is not included.
+    let files = find_files(&suite).unwrap();
+    assert_eq!(files.len(), 2);
+    assert_path_eq(&Path::new("test/globs/folder/bar.txt"), &files[0]);
+    assert_path_eq(&Path::new("test/globs/folder/woot.txt"), &files[1]);
+  }
+
+  #[test]
+  fn find_files_recursive() {
+    let suite = globs_suite("folder/**/*.txt");
+
+    let files = find_files(&suite).unwrap();
+    assert_eq!(files.len(), 3);
+    assert_path_eq(&Path::new("test/globs/folder/bar.txt"), &files[0]);
+    assert_path_eq(
+      &Path::new("test/globs/folder/nested/forgotten.txt"), &files[1]
+    );
+    assert_path_eq(&Path::new("test/globs/folder/woot.txt"), &files[2]);
+  }
+
+  #[test]
+  fn find_files_missing_root() {
+    let suite = get_suite("missing", "/overlord/does/not/exist", &[]);
+
+    assert!(find_files(&suite).is_err());
+  }
+}
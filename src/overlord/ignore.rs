@@ -0,0 +1,283 @@
+// Ignore files (`.gitignore`, `.ignore` and `.overlordignore`) used to skip
+// build output, dependencies, etc... while finding the files of a suite. Only
+// the commonly used subset of the gitignore syntax is supported: comments,
+// negation (`!`), directory only patterns (trailing `/`) and anchored patterns
+// (containing a `/`).
+use consts::{IGNORE_FILES};
+use glob::{Pattern, MatchOptions};
+use std::io::{IoResult};
+use std::io::fs::{File, PathExtensions, readdir};
+
+static MATCH_OPTIONS: MatchOptions = MatchOptions {
+  case_sensitive: true,
+  require_literal_separator: true,
+  require_literal_leading_dot: false
+};
+
+// A single (non comment) line of an ignore file.
+#[deriving(Clone)]
+struct IgnoreRule {
+  // Directory containing the ignore file. Rules only apply below it.
+  base: Path,
+
+  pattern: Pattern,
+
+  // Rule re-includes paths (`!pattern`).
+  negated: bool,
+
+  // Rule only matches directories (`pattern/`).
+  dir_only: bool,
+
+  // Rule matches the path relative to the base rather than any file name.
+  anchored: bool
+}
+
+impl IgnoreRule {
+  fn parse(base: &Path, line: &str) -> Option<IgnoreRule> {
+    let mut line = line.trim_right();
+    if line.len() == 0 || line.starts_with("#") {
+      return None
+    }
+
+    let negated = line.starts_with("!");
+    if negated {
+      line = line.slice_from(1);
+    }
+
+    let dir_only = line.ends_with("/");
+    if dir_only {
+      line = line.slice_to(line.len() - 1);
+    }
+
+    // Any (non trailing) separator anchors the pattern to the base.
+    let anchored = line.contains("/");
+    if line.starts_with("/") {
+      line = line.slice_from(1);
+    }
+
+    if line.len() == 0 {
+      return None
+    }
+
+    Some(IgnoreRule {
+      base: base.clone(),
+      pattern: Pattern::new(line),
+      negated: negated,
+      dir_only: dir_only,
+      anchored: anchored
+    })
+  }
+
+  fn matches(&self, path: &Path, is_dir: bool) -> bool {
+    if self.dir_only && !is_dir {
+      return false
+    }
+
+    if !self.base.is_ancestor_of(path) || self.base == *path {
+      return false
+    }
+
+    if self.anchored {
+      match path.path_relative_from(&self.base) {
+        Some(relative) => self.pattern.matches_path_with(
+          &relative, MATCH_OPTIONS
+        ),
+        None => false
+      }
+    } else {
+      match path.filename_str() {
+        Some(name) => self.pattern.matches_with(name, MATCH_OPTIONS),
+        None => false
+      }
+    }
+  }
+}
+
+/// The rules of every ignore file which applies to a directory.
+#[deriving(Clone)]
+pub struct IgnoreRules {
+  rules: Vec<IgnoreRule>
+}
+
+impl IgnoreRules {
+  pub fn new() -> IgnoreRules {
+    IgnoreRules { rules: Vec::new() }
+  }
+
+  /// Rules for a root directory: the ignore files of every directory from the
+  /// top of the enclosing git repository (if there is one) down to the root.
+  pub fn for_root(root: &Path) -> IoResult<IgnoreRules> {
+    let mut rules = IgnoreRules::new();
+    let mut ancestors = Vec::new();
+    let mut current = root.dir_path();
+    let mut in_repository = false;
+
+    while current != *root {
+      ancestors.push(current.clone());
+      if current.join(".git").exists() {
+        in_repository = true;
+        break
+      }
+      let parent = current.dir_path();
+      if parent == current {
+        break
+      }
+      current = parent;
+    }
+
+    if in_repository {
+      ancestors.reverse();
+      for ancestor in ancestors.iter() {
+        try!(rules.add_dir(ancestor));
+      }
+    }
+
+    try!(rules.add_dir(root));
+    Ok(rules)
+  }
+
+  /// Add the rules from the ignore files (if any) in the directory. Later
+  /// rules take precedence over earlier ones.
+  pub fn add_dir(&mut self, dir: &Path) -> IoResult<()> {
+    for name in IGNORE_FILES.iter() {
+      let path = dir.join(*name);
+      if !path.is_file() {
+        continue
+      }
+
+      let bytes = try!(File::open(&path).read_to_end());
+      let content = String::from_utf8_lossy(bytes.as_slice()).into_string();
+      self.add_lines(dir, content.as_slice());
+    }
+    Ok(())
+  }
+
+  fn add_lines(&mut self, base: &Path, content: &str) {
+    for line in content.lines() {
+      match IgnoreRule::parse(base, line) {
+        Some(rule) => self.rules.push(rule),
+        None => {}
+      }
+    }
+  }
+
+  /// Determine if the path is ignored. As with git the last matching rule
+  /// wins.
+  pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+    let mut ignored = false;
+    for rule in self.rules.iter() {
+      if rule.matches(path, is_dir) {
+        ignored = !rule.negated;
+      }
+    }
+    ignored
+  }
+}
+
+/// Every file below the root (recursively) other than those of git
+/// repositories. When `respect_ignore_files` is set ignored files are skipped
+/// and ignored directories are never entered.
+pub fn walk_files(
+  root: &Path, respect_ignore_files: bool
+) -> IoResult<Vec<Path>> {
+  let rules = if respect_ignore_files {
+    try!(IgnoreRules::for_root(root))
+  } else {
+    IgnoreRules::new()
+  };
+
+  let mut files = Vec::new();
+  try!(walk_dir(root, &rules, respect_ignore_files, &mut files));
+  Ok(files)
+}
+
+fn walk_dir(
+  dir: &Path, rules: &IgnoreRules, respect_ignore_files: bool,
+  files: &mut Vec<Path>
+) -> IoResult<()> {
+  for path in try!(readdir(dir)).iter() {
+    let is_dir = path.is_dir();
+
+    // Never look inside of the repository itself.
+    if is_dir && path.filename_str() == Some(".git") {
+      continue
+    }
+
+    if respect_ignore_files && rules.is_ignored(path, is_dir) {
+      continue
+    }
+
+    if is_dir {
+      let mut nested = rules.clone();
+      if respect_ignore_files {
+        try!(nested.add_dir(path));
+      }
+      try!(walk_dir(path, &nested, respect_ignore_files, files));
+    } else if path.is_file() {
+      files.push(path.clone());
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::{File, TempDir, UserDir};
+  use std::io::fs::{mkdir};
+  use super::{IgnoreRules, walk_files};
+
+  fn rules(content: &str) -> IgnoreRules {
+    let mut rules = IgnoreRules::new();
+    rules.add_lines(&Path::new("/foo"), content);
+    rules
+  }
+
+  #[test]
+  fn ignore_file_names() {
+    let rules = rules("# Comment\n\n*.log\n");
+    assert!(rules.is_ignored(&Path::new("/foo/a.log"), false));
+    assert!(rules.is_ignored(&Path::new("/foo/nested/a.log"), false));
+    assert!(!rules.is_ignored(&Path::new("/foo/a.txt"), false));
+    assert!(!rules.is_ignored(&Path::new("/bar/a.log"), false));
+  }
+
+  #[test]
+  fn ignore_directories_only() {
+    let rules = rules("build/\n");
+    assert!(rules.is_ignored(&Path::new("/foo/build"), true));
+    assert!(rules.is_ignored(&Path::new("/foo/nested/build"), true));
+    assert!(!rules.is_ignored(&Path::new("/foo/build"), false));
+  }
+
+  #[test]
+  fn ignore_anchored() {
+    let rules = rules("/out\nlib/*.js\n");
+    assert!(rules.is_ignored(&Path::new("/foo/out"), true));
+    assert!(!rules.is_ignored(&Path::new("/foo/nested/out"), true));
+    assert!(rules.is_ignored(&Path::new("/foo/lib/a.js"), false));
+    assert!(!rules.is_ignored(&Path::new("/foo/lib/nested/a.js"), false));
+  }
+
+  #[test]
+  fn walk_skips_git_directories() {
+    let dir = TempDir::new("overlord").unwrap();
+    let root = dir.path();
+    mkdir(&root.join(".git"), UserDir).unwrap();
+    File::create(&root.join(".git/config")).unwrap();
+    File::create(&root.join("a.txt")).unwrap();
+
+    // Even when ignore files are not respected.
+    for respect in [true, false].iter() {
+      let files = walk_files(root, *respect).unwrap();
+      assert_eq!(files.len(), 1);
+      assert!(files[0] == root.join("a.txt"));
+    }
+  }
+
+  #[test]
+  fn ignore_last_rule_wins() {
+    let rules = rules("*.log\n!keep.log\n");
+    assert!(rules.is_ignored(&Path::new("/foo/a.log"), false));
+    assert!(!rules.is_ignored(&Path::new("/foo/keep.log"), false));
+  }
+}
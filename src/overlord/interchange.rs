@@ -8,19 +8,53 @@ pub struct Manifest {
   /// Manifests may contain references to other manfiests.
   pub manifests: Option<Vec<String>>,
 
-  /// Individual suites inside of the primary manifest.
-  pub suites: Option<Vec<ManifestSuite>>
+  /// Individual suites inside of the primary manifest. Suites may also be
+  /// given as a table keyed by name (`[suites.name]`) which is converted into
+  /// this form (with the key as the `name`) before decoding.
+  pub suites: Option<Vec<ManifestSuite>>,
+
+  /// Additional paths for named suites defined by ancestor manifests.
+  pub extends: Option<Vec<ManifestExtension>>
 }
 
 /// Individual suites inside of the manifest.
 #[deriving(Decodable)]
 pub struct ManifestSuite {
-  /// The group this suite belongs to.
-  pub group: String,
+  /// Unique name of the suite (the key of named suites).
+  pub name: Option<String>,
+
+  /// Human readable title of the suite.
+  pub title: Option<String>,
+
+  /// The group this suite belongs to (defaults to the name).
+  pub group: Option<String>,
 
   /// The paths/globs to tests in this suite.
   pub paths: Vec<String>,
 
+  /// Paths/globs which are never part of the suite even when they match one
+  /// of the `paths`.
+  pub exclude: Option<Vec<String>>,
+
+  /// Skip files listed in `.gitignore`, `.ignore` and `.overlordignore` files
+  /// when finding the files of the suite. Defaults to true.
+  pub respect_ignore_files: Option<bool>,
+
   /// Executable used to run files in this suite.
-  pub executable: String
+  pub executable: String,
+
+  /// Format of the executable's output (`tap`, `tbpl`, `mozlog`, `json`).
+  /// Defaults to only using the exit code.
+  pub format: Option<String>
+}
+
+/// Paths a manifest contributes to a named suite defined by one of the
+/// manifests which (directly or indirectly) imported it.
+#[deriving(Decodable)]
+pub struct ManifestExtension {
+  /// Name of the suite to extend.
+  pub suite: String,
+
+  /// The paths/globs (relative to this manifest) added to the suite.
+  pub paths: Vec<String>
 }
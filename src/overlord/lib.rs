@@ -3,6 +3,7 @@
 extern crate glob;
 extern crate serialize;
 extern crate toml;
+extern crate time;
 #[cfg(test)]
 extern crate debug;
 
@@ -12,6 +13,15 @@ pub mod config;
 pub mod interchange;
 pub mod config_loader;
 pub mod path_identifier;
+pub mod suite_index;
+pub mod file_finder;
+pub mod ignore;
+pub mod executor;
+pub mod scheduler;
+pub mod merger;
+pub mod results;
+pub mod parser;
+pub mod reporter;
 pub mod util;
 //pub mod config;
 //pub mod suite;
@@ -0,0 +1,216 @@
+// Real time merging of the events from files running in parallel. Events from
+// the workers arrive interleaved; the merger reorders them so the events of
+// each job are contiguous while still emitting as early as possible.
+//
+// A single "active" job is streamed straight through as its tests finish.
+// Events for every other job are buffered until the active job finishes at
+// which point any jobs which already finished are flushed in full and the
+// oldest job which is still running becomes the new active job.
+use scheduler::{RunEvent};
+use std::collections::hashmap::{HashMap, HashSet};
+
+pub struct Merger {
+  // Job whose events are emitted immediately.
+  active: Option<uint>,
+
+  // Jobs (in the order they started) which are waiting to become active.
+  waiting: Vec<uint>,
+
+  // Buffered events of waiting jobs.
+  buffers: HashMap<uint, Vec<RunEvent>>,
+
+  // Waiting jobs which have already finished.
+  finished: HashSet<uint>
+}
+
+impl Merger {
+  pub fn new() -> Merger {
+    Merger {
+      active: None,
+      waiting: Vec::new(),
+      buffers: HashMap::new(),
+      finished: HashSet::new()
+    }
+  }
+
+  /// Add a single event returning the events which may now be output (in
+  /// order).
+  pub fn push(&mut self, event: RunEvent) -> Vec<RunEvent> {
+    let job = event.job();
+    let mut output = Vec::new();
+
+    if self.active.is_none() && self.waiting.len() == 0 {
+      self.active = Some(job);
+    }
+
+    if self.active == Some(job) {
+      let finished = event.is_finished();
+      output.push(event);
+      if finished {
+        self.active = None;
+        self.advance(&mut output);
+      }
+      return output;
+    }
+
+    if !self.buffers.contains_key(&job) {
+      self.waiting.push(job);
+      self.buffers.insert(job, Vec::new());
+    }
+
+    if event.is_finished() {
+      self.finished.insert(job);
+    }
+
+    match self.buffers.find_mut(&job) {
+      Some(buffer) => buffer.push(event),
+      None => {}
+    }
+    output
+  }
+
+  /// Flush all remaining buffered events. Only needed if jobs may never
+  /// finish (a worker failed, etc...).
+  pub fn finish(&mut self) -> Vec<RunEvent> {
+    let mut output = Vec::new();
+    self.active = None;
+    for job in self.waiting.iter() {
+      match self.buffers.pop(job) {
+        Some(events) => output.push_all_move(events),
+        None => {}
+      }
+    }
+    self.waiting.clear();
+    self.finished.clear();
+    output
+  }
+
+  // Pick the next active job flushing any finished jobs along the way.
+  fn advance(&mut self, output: &mut Vec<RunEvent>) {
+    loop {
+      let next = {
+        let finished = &self.finished;
+        self.waiting.iter().position(|job| finished.contains(job))
+      };
+
+      let index = match next {
+        Some(index) => index,
+        None if self.waiting.len() > 0 => 0,
+        None => return
+      };
+
+      let job = self.waiting.remove(index).unwrap();
+      match self.buffers.pop(&job) {
+        Some(events) => output.push_all_move(events),
+        None => {}
+      }
+
+      if !self.finished.remove(&job) {
+        self.active = Some(job);
+        return;
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use results::{TestResult, Pass};
+  use scheduler::{RunEvent, JobStarted, JobTest, JobFinished};
+  use error::{OverlordError};
+  use super::{Merger};
+
+  fn test(job: uint, name: &str) -> RunEvent {
+    JobTest(job, TestResult::new(name.to_string(), Pass))
+  }
+
+  fn finished(job: uint) -> RunEvent {
+    JobFinished(job, Err(OverlordError::new("done".to_string())))
+  }
+
+  // Describe events as strings so they are easy to compare.
+  fn describe(events: Vec<RunEvent>) -> Vec<String> {
+    events.iter().map(|event| {
+      match *event {
+        JobStarted(job) => format!("{} start", job),
+        JobTest(job, ref test) => format!("{} {}", job, test.name),
+        JobFinished(job, _) => format!("{} end", job)
+      }
+    }).collect()
+  }
+
+  fn push_all(merger: &mut Merger, events: Vec<RunEvent>) -> Vec<String> {
+    let mut output = Vec::new();
+    for event in events.move_iter() {
+      output.push_all_move(merger.push(event));
+    }
+    describe(output)
+  }
+
+  #[test]
+  fn active_job_streams() {
+    let mut merger = Merger::new();
+    let output = push_all(&mut merger, vec![JobStarted(0), test(0, "a")]);
+    assert_eq!(output, vec!["0 start".to_string(), "0 a".to_string()]);
+  }
+
+  #[test]
+  fn other_jobs_buffered() {
+    let mut merger = Merger::new();
+    let output = push_all(&mut merger, vec![
+      JobStarted(0),
+      JobStarted(1),
+      test(1, "b"),
+      test(0, "a")
+    ]);
+    assert_eq!(output, vec![
+      "0 start".to_string(),
+      "0 a".to_string()
+    ]);
+
+    // Once the active job finishes the next job is flushed and streamed.
+    let output = push_all(&mut merger, vec![finished(0), test(1, "c")]);
+    assert_eq!(output, vec![
+      "0 end".to_string(),
+      "1 start".to_string(),
+      "1 b".to_string(),
+      "1 c".to_string()
+    ]);
+  }
+
+  #[test]
+  fn finished_jobs_flushed_first() {
+    let mut merger = Merger::new();
+    let output = push_all(&mut merger, vec![
+      JobStarted(0),
+      JobStarted(1),
+      JobStarted(2),
+      test(2, "c"),
+      finished(2),
+      test(1, "b"),
+      finished(0),
+      finished(1)
+    ]);
+
+    assert_eq!(output, vec![
+      "0 start".to_string(),
+      "0 end".to_string(),
+      "2 start".to_string(),
+      "2 c".to_string(),
+      "2 end".to_string(),
+      "1 start".to_string(),
+      "1 b".to_string(),
+      "1 end".to_string()
+    ]);
+  }
+
+  #[test]
+  fn finish_flushes_remaining() {
+    let mut merger = Merger::new();
+    push_all(&mut merger, vec![JobStarted(0), JobStarted(1), test(1, "b")]);
+    assert_eq!(
+      describe(merger.finish()),
+      vec!["1 start".to_string(), "1 b".to_string()]
+    );
+  }
+}
@@ -0,0 +1,284 @@
+// Parser for the Overlord JSON result format (see design/json_results.md).
+// Executables may print one result object per line or a single document (an
+// array of results or an object with a `tests` array) spanning many lines.
+use parser::{OutputParser};
+use results::{TestResult, TestStatus, Pass, Fail, Skip, Todo};
+use serialize::json;
+use serialize::json::{Json};
+use std::ascii::{StrAsciiExt};
+use std::time::{Duration};
+
+pub struct JsonParser {
+  // Every line since the start of a multi line document.
+  buffer: String,
+
+  // Results of the lines in the buffer which were valid on their own (other
+  // than a trailing comma). These are reported if the buffer turns out not to
+  // be a document after all.
+  line_results: Vec<TestResult>
+}
+
+impl JsonParser {
+  pub fn new() -> JsonParser {
+    JsonParser { buffer: String::new(), line_results: Vec::new() }
+  }
+}
+
+// A line which opens a document without closing it (`{`, `[`, `{"tests": [`,
+// etc...). Other lines starting with a bracket (`[INFO] starting`) are not.
+fn starts_document(line: &str) -> bool {
+  if !line.starts_with("{") && !line.starts_with("[") {
+    return false
+  }
+  line.ends_with("{") || line.ends_with("[") || line.ends_with(",") ||
+    line.ends_with(":")
+}
+
+fn parse_line(line: &str) -> Vec<TestResult> {
+  match json::from_str(line) {
+    Ok(value) => from_json(&value),
+    Err(_) => Vec::new()
+  }
+}
+
+/// Map a status name (case insensitive) to a result status.
+pub fn status_from_str(status: &str) -> Option<TestStatus> {
+  match status.to_ascii_lower().as_slice() {
+    "pass" => Some(Pass),
+    "fail" => Some(Fail),
+    "skip" => Some(Skip),
+    "todo" => Some(Todo),
+    _ => None
+  }
+}
+
+/// Convert a parsed JSON value (a single result, a list of results or an
+/// object with a `tests` list) into results.
+pub fn from_json(value: &Json) -> Vec<TestResult> {
+  match *value {
+    json::List(ref values) => {
+      let mut results = Vec::new();
+      for value in values.iter() {
+        results.push_all_move(from_json(value));
+      }
+      results
+    },
+    json::Object(ref object) => {
+      match object.find(&"tests".to_string()) {
+        Some(tests) => from_json(tests),
+        None => to_result(value).move_iter().collect()
+      }
+    },
+    _ => Vec::new()
+  }
+}
+
+fn to_result(value: &Json) -> Option<TestResult> {
+  let object = match *value {
+    json::Object(ref object) => object,
+    _ => return None
+  };
+
+  let string_field = |key: &str| {
+    object.find(&key.to_string()).and_then(|value| value.as_string())
+  };
+
+  let (name, status) = match (string_field("name"), string_field("status")) {
+    (Some(name), Some(status)) => (name, status),
+    _ => return None
+  };
+
+  let mut result = TestResult::new(name.to_string(), Pass);
+  match status_from_str(status) {
+    Some(status) => result.status = status,
+    None => {
+      result.status = Fail;
+      result.message = Some(format!("Unknown status: '{}'", status));
+    }
+  }
+
+  match string_field("message") {
+    Some(message) => result.message = Some(message.to_string()),
+    None => {}
+  }
+
+  // Durations are in (possibly fractional) milliseconds.
+  result.duration = object.find(&"duration".to_string()).and_then(|value| {
+    value.as_f64()
+  }).map(|ms| Duration::microseconds((ms * 1000.0) as i64));
+
+  Some(result)
+}
+
+impl OutputParser for JsonParser {
+  fn feed(&mut self, line: &str) -> Vec<TestResult> {
+    let trimmed = line.trim();
+
+    if self.buffer.len() == 0 {
+      // Only the start of a multi line document is kept anything else is
+      // parsed on its own.
+      if !starts_document(trimmed) {
+        return parse_line(trimmed);
+      }
+      self.buffer.push_str(line);
+      self.buffer.push_str("\n");
+      return Vec::new();
+    }
+
+    // Lines inside of a document may also be valid on their own. Hold them
+    // back so they are not reported twice when the document is complete.
+    self.line_results.push_all_move(
+      parse_line(trimmed.trim_right_chars(','))
+    );
+    self.buffer.push_str(line);
+    self.buffer.push_str("\n");
+
+    // The document can only be complete once a bracket is closed. Output
+    // after the document is parsed line by line again.
+    if !trimmed.ends_with("}") && !trimmed.ends_with("]") {
+      return Vec::new();
+    }
+    match json::from_str(self.buffer.as_slice()) {
+      Ok(value) => {
+        self.buffer = String::new();
+        self.line_results = Vec::new();
+        from_json(&value)
+      },
+      Err(_) => Vec::new()
+    }
+  }
+
+  fn finish(&mut self) -> Vec<TestResult> {
+    // Anything left in the buffer never formed a document so fall back to
+    // whatever could be parsed line by line.
+    self.buffer = String::new();
+    let line_results = self.line_results.clone();
+    self.line_results = Vec::new();
+    line_results
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use parser::{parse_all};
+  use results::{Pass, Fail, Skip, Todo};
+  use std::time::{Duration};
+  use super::{JsonParser, status_from_str};
+
+  #[test]
+  fn statuses() {
+    assert_eq!(status_from_str("pass"), Some(Pass));
+    assert_eq!(status_from_str("FAIL"), Some(Fail));
+    assert_eq!(status_from_str("Skip"), Some(Skip));
+    assert_eq!(status_from_str("todo"), Some(Todo));
+    assert_eq!(status_from_str("ok"), None);
+  }
+
+  #[test]
+  fn parse_lines() {
+    let output = r#"starting the tests
+{"name": "one", "status": "pass", "duration": 1.5}
+{"name": "two", "status": "fail", "message": "expected 2"}
+{"status": "pass"}
+"#;
+
+    let results = parse_all(&mut JsonParser::new(), output);
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].name, "one".to_string());
+    assert_eq!(results[0].status, Pass);
+    assert_eq!(results[0].duration, Some(Duration::microseconds(1500)));
+    assert_eq!(results[1].status, Fail);
+    assert_eq!(results[1].message, Some("expected 2".to_string()));
+  }
+
+  #[test]
+  fn parse_document() {
+    let output = r#"{
+  "tests": [
+    {"name": "one", "status": "pass"},
+    {"name": "two", "status": "skip", "message": "later"}
+  ]
+}"#;
+
+    let results = parse_all(&mut JsonParser::new(), output);
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[1].status, Skip);
+    assert_eq!(results[1].message, Some("later".to_string()));
+  }
+
+  #[test]
+  fn parse_lines_with_bracketed_noise() {
+    let output = r#"[INFO] x
+{"name": "one", "status": "pass"}
+[WARN] y
+{"name": "two", "status": "fail"}
+"#;
+
+    let results = parse_all(&mut JsonParser::new(), output);
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].name, "one".to_string());
+    assert_eq!(results[1].status, Fail);
+  }
+
+  #[test]
+  fn parse_unterminated_document() {
+    // The document never ends so the lines which parsed are reported.
+    let output = r#"{"tests": [
+{"name": "one", "status": "pass"}
+{"name": "two", "status": "skip"}
+"#;
+
+    let results = parse_all(&mut JsonParser::new(), output);
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[1].status, Skip);
+  }
+
+  #[test]
+  fn parse_document_followed_by_output() {
+    let output = r#"[
+  {"name": "one", "status": "pass"},
+  {"name": "two", "status": "fail"},
+  {"name": "three", "status": "skip"}
+]
+all done
+{"name": "four", "status": "todo"}
+"#;
+
+    let results = parse_all(&mut JsonParser::new(), output);
+    assert_eq!(results.len(), 4);
+    assert_eq!(results[0].name, "one".to_string());
+    assert_eq!(results[1].status, Fail);
+    assert_eq!(results[2].status, Skip);
+    assert_eq!(results[3].name, "four".to_string());
+  }
+
+  #[test]
+  fn parse_unterminated_document_with_commas() {
+    let output = r#"[
+  {"name": "one", "status": "pass"},
+  {"name": "two", "status": "fail"},
+"#;
+
+    let results = parse_all(&mut JsonParser::new(), output);
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[1].status, Fail);
+  }
+
+  #[test]
+  fn parse_single_line_list() {
+    let output = r#"[{"name": "one", "status": "todo"}]"#;
+    let results = parse_all(&mut JsonParser::new(), output);
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].status, Todo);
+  }
+
+  #[test]
+  fn parse_unknown_status() {
+    let output = r#"{"name": "one", "status": "exploded"}"#;
+    let results = parse_all(&mut JsonParser::new(), output);
+    assert_eq!(results[0].status, Fail);
+    assert_eq!(
+      results[0].message, Some("Unknown status: 'exploded'".to_string())
+    );
+  }
+}
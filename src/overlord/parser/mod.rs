@@ -0,0 +1,42 @@
+// Parsers convert the raw output of running a file into the structured result
+// model. Parsers are "streaming" so results can be reported while the file is
+// still running.
+use config::{Format, ExitCodeFormat, TapFormat, TbplFormat, MozlogFormat,
+             JsonFormat};
+use results::{TestResult};
+
+pub mod tap;
+pub mod tbpl;
+pub mod mozlog;
+pub mod json;
+
+pub trait OutputParser {
+  /// Feed a single line of output (without the trailing newline). Any tests
+  /// which are known to be complete as of this line are returned.
+  fn feed(&mut self, line: &str) -> Vec<TestResult>;
+
+  /// Signal the end of the output and return any remaining tests.
+  fn finish(&mut self) -> Vec<TestResult>;
+}
+
+/// Parse the entire output of a file in one go.
+pub fn parse_all(parser: &mut OutputParser, output: &str) -> Vec<TestResult> {
+  let mut tests = Vec::new();
+  for line in output.lines() {
+    tests.push_all_move(parser.feed(line));
+  }
+  tests.push_all_move(parser.finish());
+  tests
+}
+
+/// Create a parser for the given format. Output which is only checked by exit
+/// code has no parser.
+pub fn for_format(format: Format) -> Option<Box<OutputParser>> {
+  match format {
+    ExitCodeFormat => None,
+    TapFormat => Some(box tap::TapParser::new() as Box<OutputParser>),
+    TbplFormat => Some(box tbpl::TbplParser::new() as Box<OutputParser>),
+    MozlogFormat => Some(box mozlog::MozlogParser::new() as Box<OutputParser>),
+    JsonFormat => Some(box json::JsonParser::new() as Box<OutputParser>)
+  }
+}
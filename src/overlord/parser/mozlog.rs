@@ -0,0 +1,233 @@
+// Parser for Mozilla structured logging (mozlog). Each line of output is a JSON
+// object with an `action`:
+//
+//   {"action": "test_start", "test": "test_a.js", "time": 1000}
+//   {"action": "test_status", "test": "test_a.js", "subtest": "one",
+//    "status": "FAIL", "expected": "PASS", "message": "nope"}
+//   {"action": "test_end", "test": "test_a.js", "status": "OK", "time": 1005}
+//
+// An `expected` field is only present when the status was not expected so a
+// missing `expected` means the status is the expected one.
+use parser::{OutputParser};
+use results::{TestResult, TestStatus, Pass, Fail, Skip, Todo};
+use serialize::json;
+use serialize::json::{Json};
+use std::collections::hashmap::HashMap;
+use std::time::{Duration};
+
+pub struct MozlogParser {
+  // Start time (ms) of every running test.
+  starts: HashMap<String, f64>,
+
+  // Messages from `log` actions since the last test started.
+  logs: Vec<String>
+}
+
+impl MozlogParser {
+  pub fn new() -> MozlogParser {
+    MozlogParser { starts: HashMap::new(), logs: Vec::new() }
+  }
+
+  /// Parse a single line of mozlog output. Lines which are not JSON or are
+  /// not results are ignored.
+  pub fn parse_line(&mut self, line: &str) -> Option<TestResult> {
+    let entry = match json::from_str(line.trim()) {
+      Ok(v) => v,
+      Err(_) => return None
+    };
+
+    let action = match string_field(&entry, "action") {
+      Some(v) => v,
+      None => return None
+    };
+
+    let test = string_field(&entry, "test").unwrap_or("");
+    let time = field(&entry, "time").and_then(|value| value.as_f64());
+
+    match action {
+      "test_start" => {
+        match time {
+          Some(time) => { self.starts.insert(test.to_string(), time); },
+          None => {}
+        }
+        self.logs.clear();
+        None
+      },
+      "log" => {
+        match string_field(&entry, "message") {
+          Some(message) => self.logs.push(message.to_string()),
+          None => {}
+        }
+        None
+      },
+      "test_status" => {
+        let subtest = string_field(&entry, "subtest").unwrap_or("");
+        let name = format!("{} | {}", test, subtest);
+        Some(to_result(name, &entry))
+      },
+      "test_end" => {
+        let mut result = to_result(test.to_string(), &entry);
+        let start = self.starts.pop(&test.to_string());
+        result.duration = match (start, time) {
+          (Some(start), Some(end)) => {
+            Some(Duration::milliseconds((end - start) as i64))
+          },
+          _ => None
+        };
+
+        if self.logs.len() > 0 {
+          result.diagnostics = Some(self.logs.connect("\n"));
+          self.logs.clear();
+        }
+        Some(result)
+      },
+      _ => None
+    }
+  }
+}
+
+/// Map an actual status (and the expected status if it differed) to a result
+/// status.
+pub fn status_from_mozlog(
+  status: &str, expected: Option<&str>, known_intermittent: &Vec<String>
+) -> TestStatus {
+  if status == "SKIP" {
+    return Skip;
+  }
+
+  let unexpected = match expected {
+    Some(expected) => {
+      expected != status &&
+        !known_intermittent.iter().any(|known| known.as_slice() == status)
+    },
+    None => false
+  };
+
+  if unexpected {
+    return Fail;
+  }
+
+  match status {
+    "PASS" | "OK" => Pass,
+    // Anything else which was expected is a known failure.
+    _ => Todo
+  }
+}
+
+fn to_result(name: String, entry: &Json) -> TestResult {
+  let status = string_field(entry, "status").unwrap_or("OK");
+  let expected = string_field(entry, "expected");
+
+  let known_intermittent: Vec<String> =
+    match field(entry, "known_intermittent") {
+      Some(&json::List(ref values)) => values.iter().filter_map(|value| {
+        value.as_string().map(|value| value.to_string())
+      }).collect(),
+      _ => Vec::new()
+    };
+
+  let mut result = TestResult::new(
+    name, status_from_mozlog(status, expected, &known_intermittent)
+  );
+
+  let message = string_field(entry, "message").map(|v| v.to_string());
+  result.message = match (result.status, expected) {
+    (Fail, Some(expected)) => {
+      let summary = format!("expected {} got {}", expected, status);
+      match message {
+        Some(message) => Some(format!("{}: {}", summary, message)),
+        None => Some(summary)
+      }
+    },
+    _ => message
+  };
+  result
+}
+
+fn field<'a>(value: &'a Json, key: &str) -> Option<&'a Json> {
+  match *value {
+    json::Object(ref object) => object.find(&key.to_string()),
+    _ => None
+  }
+}
+
+fn string_field<'a>(value: &'a Json, key: &str) -> Option<&'a str> {
+  field(value, key).and_then(|value| value.as_string())
+}
+
+impl OutputParser for MozlogParser {
+  fn feed(&mut self, line: &str) -> Vec<TestResult> {
+    self.parse_line(line).move_iter().collect()
+  }
+
+  fn finish(&mut self) -> Vec<TestResult> {
+    Vec::new()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use parser::{parse_all};
+  use results::{Pass, Fail, Skip, Todo};
+  use std::time::{Duration};
+  use super::{MozlogParser, status_from_mozlog};
+
+  #[test]
+  fn statuses() {
+    let none = Vec::new();
+    assert_eq!(status_from_mozlog("PASS", None, &none), Pass);
+    assert_eq!(status_from_mozlog("OK", None, &none), Pass);
+    assert_eq!(status_from_mozlog("FAIL", None, &none), Todo);
+    assert_eq!(status_from_mozlog("FAIL", Some("PASS"), &none), Fail);
+    assert_eq!(status_from_mozlog("PASS", Some("FAIL"), &none), Fail);
+    assert_eq!(status_from_mozlog("TIMEOUT", Some("OK"), &none), Fail);
+    assert_eq!(status_from_mozlog("SKIP", Some("OK"), &none), Skip);
+
+    let known = vec!["TIMEOUT".to_string()];
+    assert_eq!(status_from_mozlog("TIMEOUT", Some("OK"), &known), Todo);
+  }
+
+  #[test]
+  fn parse_ignored_lines() {
+    let mut parser = MozlogParser::new();
+    assert!(parser.parse_line("not json").is_none());
+    assert!(parser.parse_line("[1, 2]").is_none());
+    assert!(parser.parse_line(r#"{"action": "suite_start"}"#).is_none());
+  }
+
+  #[test]
+  fn parse_subtests() {
+    let mut parser = MozlogParser::new();
+    let result = parser.parse_line(r#"{
+      "action": "test_status", "test": "a.js", "subtest": "one",
+      "status": "FAIL", "expected": "PASS", "message": "nope"
+    }"#).unwrap();
+
+    assert_eq!(result.name, "a.js | one".to_string());
+    assert_eq!(result.status, Fail);
+    assert_eq!(
+      result.message, Some("expected PASS got FAIL: nope".to_string())
+    );
+  }
+
+  #[test]
+  fn output_parser_results() {
+    let output = r#"{"action": "suite_start", "tests": ["a.js"]}
+{"action": "test_start", "test": "a.js", "time": 1000}
+{"action": "log", "level": "INFO", "message": "starting"}
+{"action": "test_status", "test": "a.js", "subtest": "one", "status": "PASS"}
+{"action": "test_end", "test": "a.js", "status": "OK", "time": 1025}
+{"action": "suite_end"}
+"#;
+
+    let results = parse_all(&mut MozlogParser::new(), output);
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].name, "a.js | one".to_string());
+    assert_eq!(results[0].status, Pass);
+
+    assert_eq!(results[1].name, "a.js".to_string());
+    assert_eq!(results[1].status, Pass);
+    assert_eq!(results[1].duration, Some(Duration::milliseconds(25)));
+    assert_eq!(results[1].diagnostics, Some("starting".to_string()));
+  }
+}
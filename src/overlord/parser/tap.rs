@@ -0,0 +1,526 @@
+// Streaming parser for the Test Anything Protocol (versions 13 and 14). See
+// http://testanything.org/tap-version-13-specification.html
+//
+// Lines are fed one at a time. Since a YAML diagnostics block may follow a test
+// line the test line is only emitted once the next (non YAML) line is seen or
+// the output is finished.
+use parser::{OutputParser};
+use results::{TestResult, Pass, Fail, Skip, Todo};
+use std::ascii::{StrAsciiExt};
+
+#[deriving(Clone, PartialEq, Show)]
+pub enum TapDirective {
+  /// `# SKIP reason`
+  SkipDirective(String),
+
+  /// `# TODO reason`
+  TodoDirective(String)
+}
+
+/// The plan line (`1..N`).
+#[deriving(Clone, PartialEq, Show)]
+pub struct TapPlan {
+  /// Number of tests which are expected to run.
+  pub count: uint,
+
+  /// Reason given when skipping everything (`1..0 # SKIP reason`).
+  pub skip: Option<String>
+}
+
+/// A single `ok` or `not ok` line.
+#[deriving(Clone, PartialEq, Show)]
+pub struct TapTest {
+  pub ok: bool,
+
+  /// Number of the test (if the line did not include one it is inferred).
+  pub number: uint,
+
+  pub description: String,
+
+  pub directive: Option<TapDirective>,
+
+  /// Contents of the YAML block which followed the test (if any).
+  pub diagnostics: Option<String>
+}
+
+#[deriving(Clone, PartialEq, Show)]
+pub enum TapEvent {
+  /// `TAP version N`
+  Version(uint),
+
+  Plan(TapPlan),
+
+  TestLine(TapTest),
+
+  /// `Bail out! reason`
+  BailOut(Option<String>),
+
+  /// `# comment`
+  Comment(String),
+
+  /// Anything else (subtest output, stray output from the executable, etc...)
+  Unknown(String)
+}
+
+pub struct TapParser {
+  /// The plan if one has been seen.
+  pub plan: Option<TapPlan>,
+
+  /// True once a `Bail out!` line has been seen.
+  pub bailed_out: bool,
+
+  /// Number of test lines seen so far.
+  pub count: uint,
+
+  // Test line which may still be followed by a YAML block.
+  pending: Option<TapTest>,
+
+  // Lines of the YAML block currently being read.
+  yaml: Option<Vec<String>>,
+
+  // Indentation of the YAML block start marker.
+  yaml_indent: uint
+}
+
+impl TapParser {
+  pub fn new() -> TapParser {
+    TapParser {
+      plan: None,
+      bailed_out: false,
+      count: 0,
+      pending: None,
+      yaml: None,
+      yaml_indent: 0
+    }
+  }
+
+  /// Parse a single line returning any TAP events which are complete.
+  pub fn parse_line(&mut self, line: &str) -> Vec<TapEvent> {
+    let mut events = Vec::new();
+
+    if self.yaml.is_some() {
+      let trimmed = line.trim();
+      if trimmed == "..." {
+        let yaml = self.yaml.take().unwrap();
+        events.push_all_move(self.flush(Some(yaml.connect("\n"))));
+      } else {
+        let indent = self.yaml_indent;
+        let content = if leading_whitespace(line) >= indent {
+          strip_indent(line, indent)
+        } else {
+          trimmed
+        };
+        self.yaml.as_mut().unwrap().push(content.to_string());
+      }
+      return events;
+    }
+
+    // YAML blocks are only valid directly after a test line.
+    if self.pending.is_some() && line.trim() == "---" &&
+       leading_whitespace(line) > 0 {
+      self.yaml = Some(Vec::new());
+      self.yaml_indent = leading_whitespace(line);
+      return events;
+    }
+
+    events.push_all_move(self.flush(None));
+
+    match parse_test(line) {
+      Some((ok, number, description, directive)) => {
+        self.count += 1;
+        self.pending = Some(TapTest {
+          ok: ok,
+          number: number.unwrap_or(self.count),
+          description: description,
+          directive: directive,
+          diagnostics: None
+        });
+        return events;
+      },
+      None => {}
+    }
+
+    let event = if line.starts_with("TAP version ") {
+      match from_str::<uint>(line.slice_from(12).trim()) {
+        Some(version) => Version(version),
+        None => Unknown(line.to_string())
+      }
+    } else if line.starts_with("Bail out!") {
+      self.bailed_out = true;
+      let reason = line.slice_from(9).trim();
+      BailOut(if reason.len() > 0 { Some(reason.to_string()) } else { None })
+    } else if line.starts_with("#") {
+      Comment(line.slice_from(1).trim().to_string())
+    } else {
+      match parse_plan(line) {
+        Some(plan) => {
+          self.plan = Some(plan.clone());
+          Plan(plan)
+        },
+        None => Unknown(line.to_string())
+      }
+    };
+
+    events.push(event);
+    events
+  }
+
+  /// Signal the end of the output returning the final test line (if any).
+  pub fn end(&mut self) -> Vec<TapEvent> {
+    // An unterminated YAML block is still attached to the test.
+    let yaml = self.yaml.take().map(|lines| lines.connect("\n"));
+    self.flush(yaml)
+  }
+
+  fn flush(&mut self, diagnostics: Option<String>) -> Vec<TapEvent> {
+    match self.pending.take() {
+      Some(mut test) => {
+        test.diagnostics = diagnostics;
+        vec![TestLine(test)]
+      },
+      None => Vec::new()
+    }
+  }
+}
+
+// Convert TAP events into the structured result model.
+fn to_results(events: Vec<TapEvent>) -> Vec<TestResult> {
+  let mut results = Vec::new();
+  for event in events.move_iter() {
+    match event {
+      TestLine(test) => results.push(to_result(test)),
+      BailOut(reason) => {
+        let mut result = TestResult::new("Bail out!".to_string(), Fail);
+        result.message = reason;
+        results.push(result);
+      },
+      _ => {}
+    }
+  }
+  results
+}
+
+fn to_result(test: TapTest) -> TestResult {
+  let name = if test.description.len() > 0 {
+    test.description
+  } else {
+    format!("test {}", test.number)
+  };
+
+  let (status, message) = match test.directive {
+    Some(SkipDirective(reason)) => (Skip, reason),
+    Some(TodoDirective(reason)) => (Todo, reason),
+    None => (if test.ok { Pass } else { Fail }, String::new())
+  };
+
+  let mut result = TestResult::new(name, status);
+  result.message = if message.len() > 0 { Some(message) } else { None };
+  result.diagnostics = test.diagnostics;
+  result
+}
+
+impl OutputParser for TapParser {
+  fn feed(&mut self, line: &str) -> Vec<TestResult> {
+    let events = self.parse_line(line);
+    to_results(events)
+  }
+
+  fn finish(&mut self) -> Vec<TestResult> {
+    let events = self.end();
+    let mut results = to_results(events);
+
+    // Running fewer (or more) tests than planned is a failure in itself.
+    match self.plan {
+      Some(ref plan) if !self.bailed_out && plan.count != self.count => {
+        let mut result = TestResult::new("Plan".to_string(), Fail);
+        result.message = Some(format!(
+          "Planned {} tests but ran {}", plan.count, self.count
+        ));
+        results.push(result);
+      },
+      _ => {}
+    }
+    results
+  }
+}
+
+// Counted in characters rather than bytes since the whitespace is not always
+// ASCII.
+fn leading_whitespace(line: &str) -> uint {
+  line.chars().take_while(|c| c.is_whitespace()).count()
+}
+
+// Remove (up to) the first `indent` whitespace characters of the line.
+fn strip_indent(line: &str, indent: uint) -> &str {
+  for (count, (index, c)) in line.char_indices().enumerate() {
+    if count == indent || !c.is_whitespace() {
+      return line.slice_from(index);
+    }
+  }
+  ""
+}
+
+// Parse `1..N` and `1..0 # SKIP reason`.
+fn parse_plan(line: &str) -> Option<TapPlan> {
+  if !line.starts_with("1..") {
+    return None;
+  }
+
+  let rest = line.slice_from(3);
+  let (count, comment) = match rest.find('#') {
+    Some(index) => (rest.slice_to(index), Some(rest.slice_from(index + 1))),
+    None => (rest, None)
+  };
+
+  let count = match from_str::<uint>(count.trim()) {
+    Some(v) => v,
+    None => return None
+  };
+
+  let skip = match comment.and_then(|value| parse_directive(value)) {
+    Some(SkipDirective(reason)) => Some(reason),
+    _ => None
+  };
+
+  Some(TapPlan { count: count, skip: skip })
+}
+
+// Parse `ok 1 - description # directive` into its parts.
+fn parse_test(
+  line: &str
+) -> Option<(bool, Option<uint>, String, Option<TapDirective>)> {
+  let (ok, rest) = if line.starts_with("not ok") {
+    (false, line.slice_from(6))
+  } else if line.starts_with("ok") {
+    (true, line.slice_from(2))
+  } else {
+    return None;
+  };
+
+  // Rule out things like "okay".
+  if rest.len() > 0 && !rest.starts_with(" ") && !rest.starts_with("\t") {
+    return None;
+  }
+
+  let rest = rest.trim_left();
+  let digits = rest.find(|c: char| !c.is_digit()).unwrap_or(rest.len());
+  let number = from_str::<uint>(rest.slice_to(digits));
+
+  let rest = rest.slice_from(digits).trim_left();
+  let rest = if rest.starts_with("-") {
+    rest.slice_from(1).trim_left()
+  } else {
+    rest
+  };
+
+  let (description, directive) = match find_unescaped_hash(rest) {
+    Some(index) => (
+      rest.slice_to(index),
+      parse_directive(rest.slice_from(index + 1))
+    ),
+    None => (rest, None)
+  };
+
+  let description = description.trim().replace("\\#", "#");
+  Some((ok, number, description, directive))
+}
+
+// Descriptions may contain `\#` which does not start a directive.
+fn find_unescaped_hash(value: &str) -> Option<uint> {
+  let mut escaped = false;
+  for (index, c) in value.char_indices() {
+    match c {
+      '\\' => escaped = !escaped,
+      '#' if !escaped => return Some(index),
+      _ => escaped = false
+    }
+  }
+  None
+}
+
+// Directives are case insensitive and only the prefix is checked so `skipped`
+// is also a skip.
+fn parse_directive(comment: &str) -> Option<TapDirective> {
+  let comment = comment.trim();
+  let lower = comment.to_ascii_lower();
+
+  // Strip the remainder of the keyword (`ped:` in `Skipped:`) from the reason.
+  let reason = |keyword: &str| {
+    comment.slice_from(keyword.len()).trim_left_chars(|c: char| {
+      c.is_alphabetic() || c == ':'
+    }).trim().to_string()
+  };
+
+  if lower.as_slice().starts_with("skip") {
+    Some(SkipDirective(reason("skip")))
+  } else if lower.as_slice().starts_with("todo") {
+    Some(TodoDirective(reason("todo")))
+  } else {
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use parser::{parse_all};
+  use results::{Pass, Fail, Skip, Todo};
+  use super::{TapParser, TapPlan, TapTest, TestLine, Plan, Version, BailOut,
+              Comment, Unknown, SkipDirective, TodoDirective};
+
+  fn test_line(ok: bool, number: uint, description: &str) -> TapTest {
+    TapTest {
+      ok: ok,
+      number: number,
+      description: description.to_string(),
+      directive: None,
+      diagnostics: None
+    }
+  }
+
+  #[test]
+  fn parse_version_and_plan() {
+    let mut parser = TapParser::new();
+    assert_eq!(parser.parse_line("TAP version 13"), vec![Version(13)]);
+    assert_eq!(
+      parser.parse_line("1..4"),
+      vec![Plan(TapPlan { count: 4, skip: None })]
+    );
+    assert_eq!(parser.plan, Some(TapPlan { count: 4, skip: None }));
+  }
+
+  #[test]
+  fn parse_skip_all_plan() {
+    let mut parser = TapParser::new();
+    assert_eq!(
+      parser.parse_line("1..0 # Skipped: no database"),
+      vec![Plan(TapPlan { count: 0, skip: Some("no database".to_string()) })]
+    );
+  }
+
+  #[test]
+  fn test_lines_emitted_on_next_line() {
+    let mut parser = TapParser::new();
+    assert_eq!(parser.parse_line("ok 1 - first"), vec![]);
+    assert_eq!(
+      parser.parse_line("not ok 2 second"),
+      vec![TestLine(test_line(true, 1, "first"))]
+    );
+    assert_eq!(parser.end(), vec![TestLine(test_line(false, 2, "second"))]);
+  }
+
+  #[test]
+  fn test_line_numbers_inferred() {
+    let mut parser = TapParser::new();
+    parser.parse_line("ok");
+    assert_eq!(
+      parser.parse_line("ok"), vec![TestLine(test_line(true, 1, ""))]
+    );
+    assert_eq!(parser.end(), vec![TestLine(test_line(true, 2, ""))]);
+  }
+
+  #[test]
+  fn test_line_directives() {
+    let mut parser = TapParser::new();
+    parser.parse_line("ok 1 - needs network # SKIP offline");
+    let events = parser.parse_line("not ok 2 - escaped \\# hash # todo later");
+    let mut skipped = test_line(true, 1, "needs network");
+    skipped.directive = Some(SkipDirective("offline".to_string()));
+    assert_eq!(events, vec![TestLine(skipped)]);
+
+    let mut todo = test_line(false, 2, "escaped # hash");
+    todo.directive = Some(TodoDirective("later".to_string()));
+    assert_eq!(parser.end(), vec![TestLine(todo)]);
+  }
+
+  #[test]
+  fn test_line_yaml_diagnostics() {
+    let mut parser = TapParser::new();
+    parser.parse_line("not ok 1 - broken");
+    assert_eq!(parser.parse_line("  ---"), vec![]);
+    assert_eq!(parser.parse_line("  message: 'nope'"), vec![]);
+    assert_eq!(parser.parse_line("  severity: fail"), vec![]);
+
+    let mut broken = test_line(false, 1, "broken");
+    broken.diagnostics =
+      Some("message: 'nope'\nseverity: fail".to_string());
+    assert_eq!(parser.parse_line("  ..."), vec![TestLine(broken)]);
+    assert_eq!(parser.end(), vec![]);
+  }
+
+  #[test]
+  fn test_line_yaml_unicode_indent() {
+    let mut parser = TapParser::new();
+    parser.parse_line("not ok 1 - broken");
+    assert_eq!(parser.parse_line("  ---"), vec![]);
+    assert_eq!(parser.parse_line(" \u00a0message: 'nope'"), vec![]);
+    assert_eq!(parser.parse_line("   \u00a0data: 1"), vec![]);
+
+    let mut broken = test_line(false, 1, "broken");
+    broken.diagnostics = Some("message: 'nope'\n \u00a0data: 1".to_string());
+    assert_eq!(parser.parse_line("  ..."), vec![TestLine(broken)]);
+  }
+
+  #[test]
+  fn parse_bail_out_and_comments() {
+    let mut parser = TapParser::new();
+    assert_eq!(
+      parser.parse_line("# some comment"),
+      vec![Comment("some comment".to_string())]
+    );
+    assert_eq!(parser.parse_line("okay then"), vec![
+      Unknown("okay then".to_string())
+    ]);
+    assert_eq!(
+      parser.parse_line("Bail out! Database gone"),
+      vec![BailOut(Some("Database gone".to_string()))]
+    );
+    assert!(parser.bailed_out);
+  }
+
+  #[test]
+  fn output_parser_results() {
+    let output = "TAP version 13\n\
+                  1..4\n\
+                  ok 1 - passes\n\
+                  not ok 2 - fails\n  \
+                    ---\n  \
+                    got: 1\n  \
+                    ...\n\
+                  ok 3 # SKIP not today\n\
+                  not ok 4 - later # TODO\n";
+
+    let results = parse_all(&mut TapParser::new(), output);
+    assert_eq!(results.len(), 4);
+
+    assert_eq!(results[0].name, "passes".to_string());
+    assert_eq!(results[0].status, Pass);
+
+    assert_eq!(results[1].status, Fail);
+    assert_eq!(results[1].diagnostics, Some("got: 1".to_string()));
+
+    assert_eq!(results[2].name, "test 3".to_string());
+    assert_eq!(results[2].status, Skip);
+    assert_eq!(results[2].message, Some("not today".to_string()));
+
+    assert_eq!(results[3].status, Todo);
+    assert_eq!(results[3].message, None);
+  }
+
+  #[test]
+  fn output_parser_plan_mismatch() {
+    let results = parse_all(&mut TapParser::new(), "1..2\nok 1\n");
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[1].status, Fail);
+    assert_eq!(
+      results[1].message, Some("Planned 2 tests but ran 1".to_string())
+    );
+  }
+
+  #[test]
+  fn output_parser_bail_out() {
+    let results = parse_all(&mut TapParser::new(), "1..2\nok 1\nBail out!\n");
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[1].name, "Bail out!".to_string());
+    assert_eq!(results[1].status, Fail);
+  }
+}
@@ -0,0 +1,137 @@
+// Parser for the Mozilla TBPL log format. Each result is a single line:
+//
+//   TEST-PASS | path/to/test.js | message
+//   TEST-UNEXPECTED-FAIL | path/to/test.js | message
+//
+// Harnesses commonly prefix these lines with timestamps or log levels so the
+// status may appear anywhere in the line as long as it is the first field
+// (`PROCESS | 1234 | TEST-PASS | ...` is output of the test, not a result).
+use parser::{OutputParser};
+use results::{TestResult, TestStatus, Pass, Fail, Skip, Todo};
+
+pub struct TbplParser;
+
+impl TbplParser {
+  pub fn new() -> TbplParser {
+    TbplParser
+  }
+}
+
+/// Map a TBPL status (`TEST-PASS`, etc...) to a result status. Statuses which
+/// are not results (`TEST-START`, `TEST-INFO`, etc...) map to nothing.
+pub fn status_from_str(status: &str) -> Option<TestStatus> {
+  match status {
+    "TEST-PASS" => Some(Pass),
+    "TEST-KNOWN-FAIL" | "TEST-EXPECTED-FAIL" => Some(Todo),
+    "TEST-SKIP" => Some(Skip),
+    _ if status.starts_with("TEST-UNEXPECTED-") => Some(Fail),
+    _ => None
+  }
+}
+
+/// Parse a single TBPL line into a result.
+pub fn parse_line(line: &str) -> Option<TestResult> {
+  let start = match line.find_str("TEST-") {
+    Some(v) => v,
+    None => return None
+  };
+
+  if line.slice_to(start).contains("|") {
+    return None;
+  }
+
+  let mut parts = line.slice_from(start).splitn('|', 2).map(|part| {
+    part.trim()
+  });
+
+  let status = match parts.next().and_then(|part| status_from_str(part)) {
+    Some(v) => v,
+    None => return None
+  };
+
+  let name = match parts.next() {
+    Some(v) if v.len() > 0 => v.to_string(),
+    _ => return None
+  };
+
+  let mut result = TestResult::new(name, status);
+  result.message = match parts.next() {
+    Some(v) if v.len() > 0 => Some(v.to_string()),
+    _ => None
+  };
+  Some(result)
+}
+
+impl OutputParser for TbplParser {
+  fn feed(&mut self, line: &str) -> Vec<TestResult> {
+    parse_line(line).move_iter().collect()
+  }
+
+  fn finish(&mut self) -> Vec<TestResult> {
+    Vec::new()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use parser::{parse_all};
+  use results::{Pass, Fail, Skip, Todo};
+  use super::{TbplParser, parse_line, status_from_str};
+
+  #[test]
+  fn statuses() {
+    assert_eq!(status_from_str("TEST-PASS"), Some(Pass));
+    assert_eq!(status_from_str("TEST-UNEXPECTED-FAIL"), Some(Fail));
+    assert_eq!(status_from_str("TEST-UNEXPECTED-TIMEOUT"), Some(Fail));
+    assert_eq!(status_from_str("TEST-UNEXPECTED-PASS"), Some(Fail));
+    assert_eq!(status_from_str("TEST-KNOWN-FAIL"), Some(Todo));
+    assert_eq!(status_from_str("TEST-SKIP"), Some(Skip));
+    assert_eq!(status_from_str("TEST-START"), None);
+    assert_eq!(status_from_str("TEST-INFO"), None);
+  }
+
+  #[test]
+  fn parse_pass() {
+    let result = parse_line("TEST-PASS | foo/test_a.js | it works").unwrap();
+    assert_eq!(result.name, "foo/test_a.js".to_string());
+    assert_eq!(result.status, Pass);
+    assert_eq!(result.message, Some("it works".to_string()));
+  }
+
+  #[test]
+  fn parse_prefixed_failure() {
+    let line = "12:01:02  INFO -  TEST-UNEXPECTED-FAIL | test_b.js | 1 | 2";
+    let result = parse_line(line).unwrap();
+    assert_eq!(result.name, "test_b.js".to_string());
+    assert_eq!(result.status, Fail);
+    assert_eq!(result.message, Some("1 | 2".to_string()));
+  }
+
+  #[test]
+  fn parse_without_message() {
+    let result = parse_line("TEST-SKIP | test_c.js").unwrap();
+    assert_eq!(result.status, Skip);
+    assert_eq!(result.message, None);
+  }
+
+  #[test]
+  fn parse_ignored_lines() {
+    assert!(parse_line("TEST-START | test_a.js").is_none());
+    assert!(parse_line("TEST-PASS |").is_none());
+    assert!(parse_line("random output").is_none());
+    assert!(parse_line("stderr | TEST-UNEXPECTED-FAIL | a.js | x").is_none());
+  }
+
+  #[test]
+  fn output_parser_results() {
+    let output = "TEST-START | test_a.js\n\
+                  TEST-PASS | test_a.js | one\n\
+                  TEST-KNOWN-FAIL | test_a.js | two\n\
+                  TEST-END | test_a.js | finished in 2ms\n";
+
+    let results = parse_all(&mut TbplParser::new(), output);
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].status, Pass);
+    assert_eq!(results[1].status, Todo);
+  }
+}
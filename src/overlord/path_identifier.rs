@@ -1,70 +1,56 @@
-// Paths to suites are matched here...
+// Paths to suites are matched here... (see suite_index for the same matching
+// using a prebuilt index which is much faster for many suites).
 use config::{Suite};
+use std::cmp::{Equal};
 
+/// The suite which owns the path: the deepest suite (by root) whose patterns
+/// match the path. When the deepest suite does not match the path its
+/// ancestors are tried (deepest first).
 pub fn identify<'a>(
   path: &Path, suites: &'a Vec<Suite>
 ) -> Option<&'a Suite<'a>> {
+  identify_all(path, suites).move_iter().next()
+}
 
-  // Deepest meaning the mosted nested in the file system (but with the correct
-  // ancestry).
-  let mut deepest: Option<&Suite> = None;
+/// Every suite which owns the path ordered from the deepest root to the
+/// shallowest. Suites with the same root are in import order.
+pub fn identify_all<'a>(
+  path: &Path, suites: &'a Vec<Suite>
+) -> Vec<&'a Suite<'a>> {
 
-  for suite in suites.iter() {
-    let root_path = suite.root.get();
+  // Depth meaning the number of components in the root of the suite (which
+  // must be an ancestor of the path). Suites extended by other manifests have
+  // several roots and are as deep as their closest root.
+  let mut candidates: Vec<(uint, uint, &Suite)> = Vec::new();
 
+  for (index, suite) in suites.iter().enumerate() {
     // Rule out any suites which could not possibly match.
-    if !root_path.is_ancestor_of(path) {
-      continue
-    }
-
-    // If this was the first iteration continue to the next for comparision.
-    if deepest.is_none() {
-      deepest = Some(suite);
-      continue
+    match suite.root_for(path) {
+      Some(root_path) => {
+        candidates.push((root_path.components().count(), index, suite));
+      },
+      None => continue
     }
+  }
 
-    // If the root path is greater then it is now the new deepest entry.
-    if root_path > deepest.unwrap().root.get() {
-      deepest = Some(suite);
+  candidates.sort_by(|&(depth_a, index_a, _), &(depth_b, index_b, _)| {
+    match depth_b.cmp(&depth_a) {
+      Equal => index_a.cmp(&index_b),
+      order => order
     }
-  };
-
-  // No suite was even a close match based on roots.
-  if deepest.is_none() {
-    return None;
-  }
+  });
 
-  // We have the closest suite by root check if any of the paths match.
-  let suite = deepest.unwrap();
-  if suite.contains_path(path) {
-    Some(suite)
-  } else {
-    None
-  }
+  candidates.move_iter().filter(|&(_, _, suite)| {
+    suite.contains_path(path)
+  }).map(|(_, _, suite)| suite).collect()
 }
 
 #[cfg(test)]
 mod tests {
   use config::{Suite, SuitePath};
+  use test::{get_suites};
   use util::{PathWrapper};
-  use super::{identify};
-
-  fn get_suite(root_path: &str) -> Suite {
-    let root = PathWrapper::from_str(root_path);
-    let paths = vec![SuitePath::new(&root, "*.txt".to_string())];
-    Suite {
-      group: "xfoo".to_string(),
-      root: root,
-      paths: paths,
-      executable: "cat".to_string()
-    }
-  }
-
-  fn get_suites(root_paths: Vec<&str>) -> Vec<Suite> {
-    root_paths.iter().map(|root_path| {
-      get_suite(*root_path)
-    }).collect()
-  }
+  use super::{identify, identify_all};
 
   // Find a single suite by root note that this will fail the task rather then
   // using an option since it's easier and clearer for testing.
@@ -109,6 +95,64 @@ mod tests {
     );
   }
 
+  #[test]
+  fn identify_extended_suite() {
+    let mut suites = get_suites(vec!["/foo", "/foo/child"]);
+    let extension = PathWrapper::from_str("/foo/child/grandchild");
+    suites[0].paths.push(SuitePath::new(&extension, "*.txt".to_string()));
+
+    // The extension is deeper than the root of the child suite.
+    let path = Path::new("/foo/child/grandchild/woot.txt");
+    assert_eq!(identify(&path, &suites).unwrap(), &suites[0]);
+  }
+
+  #[test]
+  fn identify_none_excluded() {
+    let mut suites = get_suites(vec!["/foo"]);
+    let exclusion = SuitePath::new(&suites[0].root, "skip.txt".to_string());
+    suites[0].exclude.push(exclusion);
+
+    assert_eq!(identify(&Path::new("/foo/skip.txt"), &suites), None);
+    assert!(identify(&Path::new("/foo/woot.txt"), &suites).is_some());
+  }
+
+  #[test]
+  fn identify_falls_back_to_ancestor() {
+    let mut suites = get_suites(vec!["/foo", "/foo/nested"]);
+    suites[0].paths.push(
+      SuitePath::new(&suites[0].root, "nested/*.txt".to_string())
+    );
+    suites[1].paths = vec![
+      SuitePath::new(&suites[1].root, "*.js".to_string())
+    ];
+
+    // The deepest suite only runs javascript files.
+    let path = Path::new("/foo/nested/woot.txt");
+    assert_eq!(identify(&path, &suites).unwrap(), &suites[0]);
+
+    let path = Path::new("/foo/nested/woot.js");
+    assert_eq!(identify(&path, &suites).unwrap(), &suites[1]);
+  }
+
+  #[test]
+  fn identify_all_by_depth() {
+    let mut suites = get_suites(vec!["/foo", "/foo/a/b", "/foo/a", "/bar"]);
+    suites[0].paths = vec![
+      SuitePath::new(&suites[0].root, "**/*.txt".to_string())
+    ];
+    suites[2].paths = vec![
+      SuitePath::new(&suites[2].root, "b/*.txt".to_string())
+    ];
+
+    let found = identify_all(&Path::new("/foo/a/b/woot.txt"), &suites);
+    assert_eq!(found, vec![&suites[1], &suites[2], &suites[0]]);
+
+    let found = identify_all(&Path::new("/foo/a/woot.txt"), &suites);
+    assert_eq!(found, vec![&suites[0]]);
+
+    assert_eq!(identify_all(&Path::new("/baz/woot.txt"), &suites).len(), 0);
+  }
+
   #[test]
   fn identify_none_file_mismatch() {
     let suites = get_suites(vec!["/foo"]);
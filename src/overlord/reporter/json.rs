@@ -0,0 +1,212 @@
+// A single machine readable JSON document describing the entire run: every
+// suite, every file and every test.
+use config::{Suite};
+use reporter::{Reporter};
+use results::{TestResult, Pass, Fail, Skip, Todo};
+use scheduler::{SuiteFileResult};
+use serialize::json;
+use serialize::json::{Json};
+use std::collections::{TreeMap};
+use std::io::{IoResult};
+use std::io::process::{ExitStatus, ExitSignal};
+use std::time::{Duration};
+
+pub struct JsonReporter;
+
+fn object(fields: Vec<(&str, Json)>) -> Json {
+  let mut map = TreeMap::new();
+  for (key, value) in fields.move_iter() {
+    map.insert(key.to_string(), value);
+  }
+  json::Object(map)
+}
+
+fn string<T: Str>(value: T) -> Json {
+  json::String(value.as_slice().to_string())
+}
+
+fn optional(value: &Option<String>) -> Json {
+  match *value {
+    Some(ref value) => string(value.as_slice()),
+    None => json::Null
+  }
+}
+
+fn millis(duration: Duration) -> Json {
+  json::I64(duration.num_milliseconds())
+}
+
+fn suite_json(index: uint, suite: &Suite) -> Json {
+  object(vec![
+    ("index", json::U64(index as u64)),
+    ("name", optional(&suite.name)),
+    ("title", optional(&suite.title)),
+    ("group", string(suite.group.as_slice())),
+    ("root", string(format!("{}", suite.root))),
+    ("manifest", string(format!("{}", suite.manifest))),
+    ("manifest_index", json::U64(suite.manifest_index as u64)),
+    ("paths", json::List(suite.paths.iter().map(|path| {
+      string(path.path.as_slice())
+    }).collect())),
+    ("exclude", json::List(suite.exclude.iter().map(|path| {
+      string(path.path.as_slice())
+    }).collect())),
+    ("executable", string(suite.executable.as_slice())),
+    ("format", string(suite.format.name()))
+  ])
+}
+
+fn test_json(test: &TestResult) -> Json {
+  object(vec![
+    ("name", string(test.name.as_slice())),
+    ("status", string(test.status.name())),
+    ("message", optional(&test.message)),
+    ("diagnostics", optional(&test.diagnostics)),
+    ("duration_ms", test.duration.map(|d| millis(d)).unwrap_or(json::Null))
+  ])
+}
+
+fn file_json(file: &SuiteFileResult, tests: &Vec<TestResult>) -> Json {
+  let ref result = file.result;
+  let (exit_code, signal) = match result.status {
+    ExitStatus(code) => (json::I64(code as i64), json::Null),
+    ExitSignal(signal) => (json::Null, json::I64(signal as i64))
+  };
+
+  object(vec![
+    ("suite", json::U64(file.suite as u64)),
+    ("group", string(file.group.as_slice())),
+    ("path", string(format!("{}", result.path.display()))),
+    ("success", json::Boolean(result.success())),
+    ("exit_code", exit_code),
+    ("signal", signal),
+    ("duration_ms", millis(result.duration)),
+    ("stdout", string(result.stdout.as_slice())),
+    ("stderr", string(result.stderr.as_slice())),
+    ("tests", json::List(tests.iter().map(|test| test_json(test)).collect()))
+  ])
+}
+
+/// Build the JSON document for the run.
+pub fn to_json(suites: &Vec<Suite>, results: &Vec<SuiteFileResult>) -> Json {
+  // Counts of Pass, Fail, Skip and Todo.
+  let mut counts = [0u64, 0u64, 0u64, 0u64];
+  let mut total = Duration::zero();
+  let mut files = Vec::new();
+
+  for file in results.iter() {
+    let tests = file.result.test_results();
+    for test in tests.iter() {
+      let index = match test.status {
+        Pass => 0, Fail => 1, Skip => 2, Todo => 3
+      };
+      counts[index] += 1;
+    }
+    total = total + file.result.duration;
+    files.push(file_json(file, &tests));
+  }
+
+  let summary = object(vec![
+    ("files", json::U64(results.len() as u64)),
+    ("passed", json::U64(counts[0])),
+    ("failed", json::U64(counts[1])),
+    ("skipped", json::U64(counts[2])),
+    ("todo", json::U64(counts[3])),
+    ("duration_ms", millis(total))
+  ]);
+
+  object(vec![
+    ("suites", json::List(suites.iter().enumerate().map(|(index, suite)| {
+      suite_json(index, suite)
+    }).collect())),
+    ("files", json::List(files)),
+    ("summary", summary)
+  ])
+}
+
+impl Reporter for JsonReporter {
+  fn report(
+    &self,
+    suites: &Vec<Suite>,
+    results: &Vec<SuiteFileResult>,
+    out: &mut Writer
+  ) -> IoResult<()> {
+    writeln!(out, "{}", to_json(suites, results).to_pretty_str())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use config::{TapFormat};
+  use reporter::{Reporter};
+  use results::{TestResult, Pass, Fail};
+  use serialize::json;
+  use serialize::json::{Json};
+  use test::{get_suite, suite_file_result};
+  use std::io::{MemWriter};
+  use std::time::{Duration};
+  use super::{JsonReporter};
+
+  fn field<'a>(value: &'a Json, key: &str) -> &'a Json {
+    match *value {
+      json::Object(ref object) => object.find(&key.to_string()).unwrap(),
+      _ => fail!("not an object")
+    }
+  }
+
+  fn item<'a>(value: &'a Json, index: uint) -> &'a Json {
+    match *value {
+      json::List(ref values) => &values[index],
+      _ => fail!("not a list")
+    }
+  }
+
+  #[test]
+  fn report_document() {
+    let mut suite = get_suite("unit", "/foo", &["*.tap"]);
+    suite.format = TapFormat;
+    let suites = vec![suite];
+
+    let mut failed = TestResult::new("two".to_string(), Fail);
+    failed.message = Some("nope".to_string());
+
+    let tests = vec![TestResult::new("one".to_string(), Pass), failed];
+    let mut file = suite_file_result(0, "/foo/a.tap", 1, tests);
+    file.result.stdout = "ok 1 - one\nnot ok 2 - two\n".to_string();
+    file.result.duration = Duration::milliseconds(12);
+    let results = vec![file];
+
+    let mut out = MemWriter::new();
+    JsonReporter.report(&suites, &results, &mut out).unwrap();
+    let output = String::from_utf8(out.unwrap()).unwrap();
+    let document = json::from_str(output.as_slice()).unwrap();
+
+    let suite = item(field(&document, "suites"), 0);
+    assert_eq!(field(suite, "group").as_string(), Some("unit"));
+    assert!(field(suite, "name").is_null());
+    assert_eq!(field(suite, "root").as_string(), Some("/foo"));
+    assert_eq!(field(suite, "executable").as_string(), Some("cat"));
+    assert_eq!(field(suite, "format").as_string(), Some("tap"));
+    assert_eq!(
+      field(suite, "manifest").as_string(), Some("/foo/overlord.toml")
+    );
+    assert_eq!(field(suite, "manifest_index").as_i64(), Some(0));
+
+    let file = item(field(&document, "files"), 0);
+    assert_eq!(field(file, "path").as_string(), Some("/foo/a.tap"));
+    assert_eq!(field(file, "success").as_boolean(), Some(false));
+    assert_eq!(field(file, "exit_code").as_i64(), Some(1));
+    assert_eq!(field(file, "duration_ms").as_i64(), Some(12));
+
+    let test = item(field(file, "tests"), 1);
+    assert_eq!(field(test, "name").as_string(), Some("two"));
+    assert_eq!(field(test, "status").as_string(), Some("fail"));
+    assert_eq!(field(test, "message").as_string(), Some("nope"));
+    assert!(field(test, "duration_ms").is_null());
+
+    let summary = field(&document, "summary");
+    assert_eq!(field(summary, "files").as_i64(), Some(1));
+    assert_eq!(field(summary, "passed").as_i64(), Some(1));
+    assert_eq!(field(summary, "failed").as_i64(), Some(1));
+  }
+}
@@ -0,0 +1,278 @@
+// JUnit XML output for CI systems (Jenkins, GitLab, etc...). Each suite is a
+// `<testsuite>` and each test (or file when only the exit code is known) is a
+// `<testcase>`.
+use config::{Suite};
+use reporter::{Reporter};
+use results::{TestResult, Fail, Skip, Todo};
+use scheduler::{SuiteFileResult};
+use std::io::{IoResult};
+use std::time::{Duration};
+
+pub struct JunitReporter;
+
+/// Escape text for use in XML attributes and content. Control characters
+/// (other than tabs and newlines) are not allowed in XML at all so they are
+/// dropped (e.g. the escape codes of colored output).
+pub fn escape(value: &str) -> String {
+  let mut escaped = String::with_capacity(value.len());
+  for c in value.chars() {
+    match c {
+      '&' => escaped.push_str("&amp;"),
+      '<' => escaped.push_str("&lt;"),
+      '>' => escaped.push_str("&gt;"),
+      '"' => escaped.push_str("&quot;"),
+      '\'' => escaped.push_str("&apos;"),
+      '\t' | '\n' | '\r' => escaped.push(c),
+      _ if (c as u32) < 0x20 => {},
+      _ => escaped.push(c)
+    }
+  }
+  escaped
+}
+
+fn seconds(duration: Duration) -> String {
+  format!("{:.3}", duration.num_milliseconds() as f64 / 1000.0)
+}
+
+fn suite_name(suite: &Suite) -> String {
+  format!("{} ({})", suite.group, suite.root)
+}
+
+// Paths are relative to the suite root (if possible) and used as the class
+// name of every test in the file.
+fn class_name(suite: &Suite, file: &SuiteFileResult) -> String {
+  let ref path = file.result.path;
+  let relative = path.path_relative_from(suite.root.get());
+  format!("{}", relative.as_ref().unwrap_or(path).display())
+}
+
+fn write_testcase(
+  out: &mut Writer,
+  class_name: &str,
+  file: &SuiteFileResult,
+  test: &TestResult
+) -> IoResult<()> {
+  let time = test.duration.unwrap_or(Duration::zero());
+  try!(write!(
+    out,
+    "    <testcase name=\"{}\" classname=\"{}\" time=\"{}\"",
+    escape(test.name.as_slice()),
+    escape(class_name),
+    seconds(time)
+  ));
+
+  let message = match test.message {
+    Some(ref message) => escape(message.as_slice()),
+    None => String::new()
+  };
+  match test.status {
+    Fail => {
+      try!(writeln!(out, ">"));
+      try!(write!(out, "      <failure message=\"{}\">", message));
+
+      // The captured output is the most useful detail for a failure.
+      let mut details = Vec::new();
+      match test.diagnostics {
+        Some(ref diagnostics) => details.push(diagnostics.clone()),
+        None => {}
+      }
+      if file.result.stdout.len() > 0 {
+        details.push(file.result.stdout.clone());
+      }
+      if file.result.stderr.len() > 0 {
+        details.push(file.result.stderr.clone());
+      }
+      try!(write!(out, "{}", escape(details.connect("\n").as_slice())));
+
+      try!(writeln!(out, "</failure>"));
+      writeln!(out, "    </testcase>")
+    },
+    Skip | Todo => {
+      try!(writeln!(out, ">"));
+      try!(writeln!(out, "      <skipped message=\"{}\"/>", message));
+      writeln!(out, "    </testcase>")
+    },
+    _ => writeln!(out, "/>")
+  }
+}
+
+impl Reporter for JunitReporter {
+  fn report(
+    &self,
+    suites: &Vec<Suite>,
+    results: &Vec<SuiteFileResult>,
+    out: &mut Writer
+  ) -> IoResult<()> {
+    try!(writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>"));
+    try!(writeln!(out, "<testsuites>"));
+
+    for (index, suite) in suites.iter().enumerate() {
+      let files: Vec<(&SuiteFileResult, Vec<TestResult>)> =
+        results.iter().filter(|file| file.suite == index).map(|file| {
+          (file, file.result.test_results())
+        }).collect();
+
+      // Suites which were not run are left out entirely.
+      if files.len() == 0 {
+        continue;
+      }
+
+      let mut tests = 0u;
+      let mut failures = 0u;
+      let mut skipped = 0u;
+      let mut time = Duration::zero();
+      for &(file, ref file_tests) in files.iter() {
+        time = time + file.result.duration;
+        for test in file_tests.iter() {
+          tests += 1;
+          match test.status {
+            Fail => failures += 1,
+            Skip | Todo => skipped += 1,
+            _ => {}
+          }
+        }
+      }
+
+      try!(writeln!(
+        out,
+        "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" \
+         skipped=\"{}\" time=\"{}\">",
+        escape(suite_name(suite).as_slice()),
+        tests,
+        failures,
+        skipped,
+        seconds(time)
+      ));
+
+      try!(writeln!(out, "    <properties>"));
+      try!(writeln!(
+        out,
+        "      <property name=\"manifest\" value=\"{}\"/>",
+        escape(format!("{}", suite.manifest).as_slice())
+      ));
+      try!(writeln!(out, "    </properties>"));
+
+      for &(file, ref file_tests) in files.iter() {
+        let class_name = class_name(suite, file);
+        for test in file_tests.iter() {
+          try!(write_testcase(out, class_name.as_slice(), file, test));
+        }
+      }
+
+      try!(writeln!(out, "  </testsuite>"));
+    }
+
+    writeln!(out, "</testsuites>")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use config::{Suite};
+  use reporter::{Reporter};
+  use results::{TestResult, Pass, Fail, Skip};
+  use scheduler::{SuiteFileResult};
+  use test;
+  use std::io::{MemWriter};
+  use std::time::{Duration};
+  use super::{JunitReporter, escape};
+
+  fn get_suite<'a>(group: &str, root: &str) -> Suite<'a> {
+    test::get_suite(group, root, &["*.js"])
+  }
+
+  fn file_result(
+    suite: uint, path: &str, code: int, tests: Vec<TestResult>
+  ) -> SuiteFileResult {
+    let mut file = test::suite_file_result(suite, path, code, tests);
+    file.result.duration = Duration::milliseconds(1500);
+    if code != 0 {
+      file.result.stderr = "a < b".to_string();
+    }
+    file
+  }
+
+  #[test]
+  fn escape_xml() {
+    assert_eq!(
+      escape("<a href=\"x\">'&'</a>"),
+      "&lt;a href=&quot;x&quot;&gt;&apos;&amp;&apos;&lt;/a&gt;".to_string()
+    );
+  }
+
+  #[test]
+  fn escape_control_characters() {
+    assert_eq!(
+      escape("\x1b[31mred\x1b[0m\tok\r\n\x00"),
+      "[31mred[0m\tok\r\n".to_string()
+    );
+  }
+
+  #[test]
+  fn report_colored_stderr() {
+    let suites = vec![get_suite("unit", "/foo")];
+    let mut result = file_result(0, "/foo/a.js", 1, Vec::new());
+    result.result.stderr = "\x1b[31mnot ok\x1b[0m".to_string();
+
+    let mut out = MemWriter::new();
+    JunitReporter.report(&suites, &vec![result], &mut out).unwrap();
+    let output = String::from_utf8(out.unwrap()).unwrap();
+    assert!(output.as_slice().contains("[31mnot ok[0m</failure>"));
+    assert!(!output.as_slice().contains("\x1b"));
+  }
+
+  #[test]
+  fn report_suites() {
+    let suites = vec![
+      get_suite("unit", "/foo"),
+      get_suite("unit", "/foo/nested"),
+      get_suite("integration", "/bar")
+    ];
+
+    let mut failed = TestResult::new("two".to_string(), Fail);
+    failed.message = Some("expected \"2\"".to_string());
+    let mut skipped = TestResult::new("three".to_string(), Skip);
+    skipped.duration = Some(Duration::milliseconds(20));
+
+    let results = vec![
+      file_result(0, "/foo/a.js", 0, Vec::new()),
+      file_result(2, "/bar/b.js", 1, vec![
+        TestResult::new("one".to_string(), Pass),
+        failed,
+        skipped
+      ])
+    ];
+
+    let expected = vec![
+      "<?xml version=\"1.0\" encoding=\"UTF-8\"?>",
+      "<testsuites>",
+      "  <testsuite name=\"unit (/foo)\" tests=\"1\" failures=\"0\" \
+       skipped=\"0\" time=\"1.500\">",
+      "    <properties>",
+      "      <property name=\"manifest\" value=\"/foo/overlord.toml\"/>",
+      "    </properties>",
+      "    <testcase name=\"/foo/a.js\" classname=\"a.js\" time=\"1.500\"/>",
+      "  </testsuite>",
+      "  <testsuite name=\"integration (/bar)\" tests=\"3\" failures=\"1\" \
+       skipped=\"1\" time=\"1.500\">",
+      "    <properties>",
+      "      <property name=\"manifest\" value=\"/bar/overlord.toml\"/>",
+      "    </properties>",
+      "    <testcase name=\"one\" classname=\"b.js\" time=\"0.000\"/>",
+      "    <testcase name=\"two\" classname=\"b.js\" time=\"0.000\">",
+      "      <failure message=\"expected &quot;2&quot;\">a &lt; b</failure>",
+      "    </testcase>",
+      "    <testcase name=\"three\" classname=\"b.js\" time=\"0.020\">",
+      "      <skipped message=\"\"/>",
+      "    </testcase>",
+      "  </testsuite>",
+      "</testsuites>",
+      ""
+    ].connect("\n");
+
+    let mut out = MemWriter::new();
+    JunitReporter.report(&suites, &results, &mut out).unwrap();
+    let output = String::from_utf8(out.unwrap()).unwrap();
+    assert_eq!(output, expected);
+  }
+}
@@ -0,0 +1,35 @@
+// Reporters serialize the aggregated results of a run into some output format.
+// Reporters only deal with the structured result model so any parsed format
+// can be converted into any output format.
+use config::{Suite};
+use scheduler::{SuiteFileResult};
+use std::io::{IoResult};
+
+pub mod json;
+pub mod junit;
+pub mod pretty;
+pub mod tap;
+pub mod tbpl;
+
+pub trait Reporter {
+  /// Write the results of an entire run. `results` are attributed to `suites`
+  /// by index.
+  fn report(
+    &self,
+    suites: &Vec<Suite>,
+    results: &Vec<SuiteFileResult>,
+    out: &mut Writer
+  ) -> IoResult<()>;
+}
+
+/// Find a reporter by the name used on the command line.
+pub fn for_name(name: &str) -> Option<Box<Reporter>> {
+  match name {
+    "json" => Some(box json::JsonReporter as Box<Reporter>),
+    "junit" => Some(box junit::JunitReporter as Box<Reporter>),
+    "pretty" => Some(box pretty::PrettyReporter::new() as Box<Reporter>),
+    "tap" => Some(box tap::TapReporter as Box<Reporter>),
+    "tbpl" => Some(box tbpl::TbplReporter as Box<Reporter>),
+    _ => None
+  }
+}
@@ -0,0 +1,278 @@
+// Human readable (optionally colored) output. Results are grouped by suite
+// with the details of every failure repeated at the end so they are easy to
+// find in long runs.
+use config::{Suite};
+use reporter::{Reporter};
+use results::{TestResult, Pass, Fail, Skip, Todo};
+use scheduler::{SuiteFileResult};
+use std::io::{IoResult};
+use std::io::stdio;
+
+static RED: &'static str = "31";
+static GREEN: &'static str = "32";
+static YELLOW: &'static str = "33";
+static CYAN: &'static str = "36";
+static BOLD: &'static str = "1";
+
+pub struct PrettyReporter {
+  /// Use ANSI colors in the output.
+  pub color: bool
+}
+
+impl PrettyReporter {
+  /// Create a reporter which only uses color when stdout is a terminal.
+  pub fn new() -> PrettyReporter {
+    PrettyReporter { color: stdio::stdout_raw().isatty() }
+  }
+
+  fn paint(&self, code: &str, text: &str) -> String {
+    if self.color {
+      format!("\x1b[{}m{}\x1b[0m", code, text)
+    } else {
+      text.to_string()
+    }
+  }
+
+  fn status(&self, test: &TestResult) -> String {
+    match test.status {
+      Pass => self.paint(GREEN, "PASS"),
+      Fail => self.paint(RED, "FAIL"),
+      Skip => self.paint(YELLOW, "SKIP"),
+      Todo => self.paint(CYAN, "TODO")
+    }
+  }
+}
+
+// Display paths relative to the root of the suite where possible.
+fn relative_path(suite: Option<&Suite>, path: &Path) -> String {
+  let relative = suite.and_then(|suite| {
+    path.path_relative_from(suite.root.get())
+  });
+  format!("{}", relative.as_ref().unwrap_or(path).display())
+}
+
+impl Reporter for PrettyReporter {
+  fn report(
+    &self,
+    suites: &Vec<Suite>,
+    results: &Vec<SuiteFileResult>,
+    out: &mut Writer
+  ) -> IoResult<()> {
+    // Counts of Pass, Fail, Skip and Todo.
+    let mut counts = [0u, 0u, 0u, 0u];
+    let mut failures: Vec<(&SuiteFileResult, TestResult)> = Vec::new();
+    let mut current_suite = None;
+
+    // Results of different suites may be interleaved (explicit files, etc...)
+    // so files are grouped by suite in the order each suite is first seen.
+    let mut order: Vec<uint> = Vec::new();
+    for file in results.iter() {
+      if !order.contains(&file.suite) {
+        order.push(file.suite);
+      }
+    }
+    let mut grouped: Vec<&SuiteFileResult> = Vec::new();
+    for suite in order.iter() {
+      for file in results.iter().filter(|file| file.suite == *suite) {
+        grouped.push(file);
+      }
+    }
+
+    for file in grouped.move_iter() {
+      let suite = if file.suite < suites.len() {
+        Some(&suites[file.suite])
+      } else {
+        None
+      };
+
+      if current_suite != Some(file.suite) {
+        current_suite = Some(file.suite);
+        let source = match suite {
+          Some(suite) => format!(
+            " ({}) from {} (#{})",
+            suite.root,
+            suite.manifest,
+            suite.manifest_index
+          ),
+          None => String::new()
+        };
+        let header = format!("{}{}", file.group, source);
+        try!(writeln!(out, "{}", self.paint(BOLD, header.as_slice())));
+      }
+
+      let tests = file.result.test_results();
+      let file_status = if file.result.success() {
+        self.paint(GREEN, "PASS")
+      } else {
+        self.paint(RED, "FAIL")
+      };
+
+      try!(writeln!(
+        out,
+        "  {} {} ({}ms)",
+        file_status,
+        relative_path(suite, &file.result.path),
+        file.result.duration.num_milliseconds()
+      ));
+
+      // Files with only the exit code have nothing more to show.
+      let show_tests = file.result.tests.len() > 0;
+      for test in tests.move_iter() {
+        let index = match test.status {
+          Pass => 0, Fail => 1, Skip => 2, Todo => 3
+        };
+        counts[index] += 1;
+
+        if show_tests {
+          let duration = match test.duration {
+            Some(duration) => format!(" ({}ms)", duration.num_milliseconds()),
+            None => String::new()
+          };
+          try!(writeln!(
+            out, "    {} {}{}", self.status(&test), test.name, duration
+          ));
+        }
+
+        if test.failed() {
+          failures.push((file, test));
+        }
+      }
+    }
+
+    if failures.len() > 0 {
+      try!(writeln!(out, "\n{}", self.paint(BOLD, "Failures:")));
+    }
+
+    for (number, &(file, ref test)) in failures.iter().enumerate() {
+      try!(writeln!(
+        out,
+        "\n  {}) {} {} {}",
+        number + 1,
+        file.group,
+        file.result.path.display(),
+        self.paint(RED, test.name.as_slice())
+      ));
+
+      for value in [&test.message, &test.diagnostics].iter() {
+        match **value {
+          Some(ref value) => {
+            for line in value.as_slice().lines() {
+              try!(writeln!(out, "     {}", line));
+            }
+          },
+          None => {}
+        }
+      }
+
+      for line in file.result.stderr.as_slice().lines() {
+        try!(writeln!(out, "     {}", self.paint(YELLOW, line)));
+      }
+    }
+
+    let summary = format!(
+      "{} passed, {} failed, {} skipped, {} todo",
+      counts[0], counts[1], counts[2], counts[3]
+    );
+    let color = if counts[1] > 0 { RED } else { GREEN };
+    writeln!(out, "\n{}", self.paint(color, summary.as_slice()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use reporter::{Reporter};
+  use results::{TestResult, Pass, Fail, Skip};
+  use scheduler::{SuiteFileResult};
+  use test::{get_suite, suite_file_result};
+  use std::io::{MemWriter};
+  use std::time::{Duration};
+  use super::{PrettyReporter};
+
+  fn file_result(
+    path: &str, code: int, tests: Vec<TestResult>
+  ) -> SuiteFileResult {
+    let mut file = suite_file_result(0, path, code, tests);
+    file.result.duration = Duration::milliseconds(4);
+    if code != 0 {
+      file.result.stderr = "oops".to_string();
+    }
+    file
+  }
+
+  fn report(
+    reporter: PrettyReporter, results: Vec<SuiteFileResult>
+  ) -> String {
+    let suites = vec![
+      get_suite("unit", "/foo", &["*.txt"]),
+      get_suite("lint", "/bar", &["*.txt"])
+    ];
+    let mut out = MemWriter::new();
+    reporter.report(&suites, &results, &mut out).unwrap();
+    String::from_utf8(out.unwrap()).unwrap()
+  }
+
+  #[test]
+  fn report_grouped_by_suite() {
+    let mut failed = TestResult::new("two".to_string(), Fail);
+    failed.message = Some("expected 2".to_string());
+
+    let results = vec![
+      file_result("/foo/a.txt", 0, Vec::new()),
+      file_result("/foo/b.txt", 1, vec![
+        TestResult::new("one".to_string(), Pass),
+        failed,
+        TestResult::new("three".to_string(), Skip)
+      ])
+    ];
+
+    let expected = "unit (/foo) from /foo/overlord.toml (#0)\n  \
+                      PASS a.txt (4ms)\n  \
+                      FAIL b.txt (4ms)\n    \
+                        PASS one\n    \
+                        FAIL two\n    \
+                        SKIP three\n\
+                    \n\
+                    Failures:\n\
+                    \n  \
+                      1) unit /foo/b.txt two\n     \
+                         expected 2\n     \
+                         oops\n\
+                    \n\
+                    2 passed, 1 failed, 1 skipped, 0 todo\n";
+
+    let output = report(PrettyReporter { color: false }, results);
+    assert_eq!(output, expected.to_string());
+  }
+
+  #[test]
+  fn report_interleaved_suites() {
+    let mut other = suite_file_result(1, "/bar/c.txt", 0, Vec::new());
+    other.group = "lint".to_string();
+    let results = vec![
+      file_result("/foo/a.txt", 0, Vec::new()),
+      other,
+      file_result("/foo/b.txt", 0, Vec::new())
+    ];
+
+    let expected = "unit (/foo) from /foo/overlord.toml (#0)\n  \
+                      PASS a.txt (4ms)\n  \
+                      PASS b.txt (4ms)\n\
+                    lint (/bar) from /bar/overlord.toml (#0)\n  \
+                      PASS c.txt (0ms)\n\
+                    \n\
+                    3 passed, 0 failed, 0 skipped, 0 todo\n";
+
+    let output = report(PrettyReporter { color: false }, results);
+    assert_eq!(output, expected.to_string());
+  }
+
+  #[test]
+  fn report_with_color() {
+    let results = vec![file_result("/foo/a.txt", 0, Vec::new())];
+    let output = report(PrettyReporter { color: true }, results);
+    assert!(output.as_slice().contains("\x1b[32mPASS\x1b[0m"));
+    assert!(output.as_slice().contains(
+      "\x1b[1munit (/foo) from /foo/overlord.toml (#0)\x1b[0m"
+    ));
+  }
+}
@@ -0,0 +1,237 @@
+// Serialize the results of every file into a single TAP (version 13) stream.
+// Tests are renumbered across all files and each test carries a YAML
+// diagnostics block identifying where it came from (including the manifest
+// which defined its suite).
+use config::{Suite};
+use reporter::{Reporter};
+use results::{TestResult, Pass, Fail, Skip, Todo};
+use scheduler::{SuiteFileResult};
+use std::io::{IoResult};
+
+pub struct TapReporter;
+
+impl Reporter for TapReporter {
+  fn report(
+    &self,
+    suites: &Vec<Suite>,
+    results: &Vec<SuiteFileResult>,
+    out: &mut Writer
+  ) -> IoResult<()> {
+    let files: Vec<(&SuiteFileResult, Vec<TestResult>)> =
+      results.iter().map(|file| (file, file.result.test_results())).collect();
+
+    let total = files.iter().fold(0, |sum, &(_, ref tests)| sum + tests.len());
+
+    try!(writeln!(out, "TAP version 13"));
+    try!(writeln!(out, "1..{}", total));
+
+    let mut number = 0u;
+    for &(file, ref tests) in files.iter() {
+      let suite = if file.suite < suites.len() {
+        Some(&suites[file.suite])
+      } else {
+        None
+      };
+
+      for test in tests.iter() {
+        number += 1;
+        try!(write_test(out, number, suite, file, test));
+      }
+    }
+    Ok(())
+  }
+}
+
+fn write_test(
+  out: &mut Writer,
+  number: uint,
+  suite: Option<&Suite>,
+  file: &SuiteFileResult,
+  test: &TestResult
+) -> IoResult<()> {
+  let ok = match test.status {
+    Fail | Todo => "not ok",
+    Pass | Skip => "ok"
+  };
+
+  let description = escape_line(test.name.replace("#", "\\#").as_slice());
+  let reason = match test.message {
+    Some(ref message) => escape_line(message.as_slice()),
+    None => String::new()
+  };
+  let directive = match test.status {
+    Skip => format!(" # SKIP {}", reason),
+    Todo => format!(" # TODO {}", reason),
+    _ => String::new()
+  };
+  let directive = directive.as_slice().trim_right();
+
+  try!(writeln!(out, "{} {} - {}{}", ok, number, description, directive));
+
+  try!(writeln!(out, "  ---"));
+  try!(writeln!(out, "  group: {}", yaml_string(file.group.as_slice())));
+  try!(writeln!(
+    out,
+    "  file: {}",
+    yaml_string(format!("{}", file.result.path.display()).as_slice())
+  ));
+
+  match suite {
+    Some(suite) => {
+      try!(writeln!(
+        out,
+        "  manifest: {}",
+        yaml_string(format!("{}", suite.manifest).as_slice())
+      ));
+      try!(writeln!(out, "  manifest_index: {}", suite.manifest_index));
+    },
+    None => {}
+  }
+
+  match test.duration {
+    Some(duration) => {
+      try!(writeln!(out, "  duration_ms: {}", duration.num_milliseconds()));
+    },
+    None => {}
+  }
+
+  if test.failed() {
+    match test.message {
+      Some(ref message) => try!(write_block(out, "message", message)),
+      None => {}
+    }
+
+    match test.diagnostics {
+      Some(ref diagnostics) => {
+        try!(write_block(out, "diagnostics", diagnostics))
+      },
+      None => {}
+    }
+
+    if file.result.stderr.len() > 0 {
+      try!(write_block(out, "stderr", &file.result.stderr));
+    }
+  }
+
+  writeln!(out, "  ...")
+}
+
+// Test lines can not span multiple lines.
+fn escape_line(value: &str) -> String {
+  value.replace("\r", "\\r").replace("\n", "\\n")
+}
+
+// Multi-line values are written as YAML literal blocks.
+fn write_block(out: &mut Writer, key: &str, value: &String) -> IoResult<()> {
+  try!(writeln!(out, "  {}: |", key));
+  for line in value.as_slice().lines() {
+    try!(writeln!(out, "    {}", line));
+  }
+  Ok(())
+}
+
+fn yaml_string(value: &str) -> String {
+  format!("\"{}\"", value.replace("\\", "\\\\").replace("\"", "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+  use reporter::{Reporter};
+  use results::{TestResult, Pass, Fail, Skip};
+  use scheduler::{SuiteFileResult};
+  use test::{get_suite, suite_file_result};
+  use std::io::{MemWriter};
+  use std::time::{Duration};
+  use super::{TapReporter};
+
+  fn file_result(
+    path: &str, code: int, stderr: &str, tests: Vec<TestResult>
+  ) -> SuiteFileResult {
+    let mut file = suite_file_result(0, path, code, tests);
+    file.result.stderr = stderr.to_string();
+    file.result.duration = Duration::milliseconds(3);
+    file
+  }
+
+  fn report(results: Vec<SuiteFileResult>) -> String {
+    let mut out = MemWriter::new();
+    let suites = vec![get_suite("unit", "/foo", &["*.txt"])];
+    TapReporter.report(&suites, &results, &mut out).unwrap();
+    String::from_utf8(out.unwrap()).unwrap()
+  }
+
+  #[test]
+  fn report_empty() {
+    assert_eq!(report(Vec::new()), "TAP version 13\n1..0\n".to_string());
+  }
+
+  #[test]
+  fn report_multi_line_names() {
+    let mut skipped = TestResult::new("one\ntwo".to_string(), Skip);
+    skipped.message = Some("not\r\nnow".to_string());
+    let output = report(vec![file_result("/foo/a.txt", 0, "", vec![skipped])]);
+
+    assert!(output.as_slice().contains(
+      "ok 1 - one\\ntwo # SKIP not\\r\\nnow\n"
+    ));
+    assert_eq!(output.as_slice().lines().filter(|line| {
+      line.starts_with("ok ")
+    }).count(), 1);
+  }
+
+  #[test]
+  fn report_renumbers_across_files() {
+    let mut skipped = TestResult::new("later".to_string(), Skip);
+    skipped.message = Some("no network".to_string());
+    let mut failed = TestResult::new("two # three".to_string(), Fail);
+    failed.message = Some("expected 2".to_string());
+
+    let results = vec![
+      file_result("/foo/a.txt", 0, "", Vec::new()),
+      file_result(
+        "/foo/b.txt",
+        1,
+        "oops\n",
+        vec![TestResult::new("one".to_string(), Pass), failed, skipped]
+      )
+    ];
+
+    let expected = "TAP version 13\n\
+                    1..4\n\
+                    ok 1 - /foo/a.txt\n  \
+                      ---\n  \
+                      group: \"unit\"\n  \
+                      file: \"/foo/a.txt\"\n  \
+                      manifest: \"/foo/overlord.toml\"\n  \
+                      manifest_index: 0\n  \
+                      duration_ms: 3\n  \
+                      ...\n\
+                    ok 2 - one\n  \
+                      ---\n  \
+                      group: \"unit\"\n  \
+                      file: \"/foo/b.txt\"\n  \
+                      manifest: \"/foo/overlord.toml\"\n  \
+                      manifest_index: 0\n  \
+                      ...\n\
+                    not ok 3 - two \\# three\n  \
+                      ---\n  \
+                      group: \"unit\"\n  \
+                      file: \"/foo/b.txt\"\n  \
+                      manifest: \"/foo/overlord.toml\"\n  \
+                      manifest_index: 0\n  \
+                      message: |\n    \
+                        expected 2\n  \
+                      stderr: |\n    \
+                        oops\n  \
+                      ...\n\
+                    ok 4 - later # SKIP no network\n  \
+                      ---\n  \
+                      group: \"unit\"\n  \
+                      file: \"/foo/b.txt\"\n  \
+                      manifest: \"/foo/overlord.toml\"\n  \
+                      manifest_index: 0\n  \
+                      ...\n";
+
+    assert_eq!(report(results), expected.to_string());
+  }
+}
@@ -0,0 +1,180 @@
+// Serialize the results of a run as Mozilla TBPL log lines so existing log
+// parsers (Treeherder, etc...) can read Overlord runs. Each run of files from
+// the same suite starts with a `SUITE-START` line naming its manifest.
+use config::{Suite};
+use reporter::{Reporter};
+use results::{TestResult, Pass, Fail, Skip, Todo};
+use scheduler::{SuiteFileResult};
+use std::io::{IoResult};
+
+pub struct TbplReporter;
+
+/// The TBPL status for a result.
+pub fn status_to_str(test: &TestResult) -> &'static str {
+  match test.status {
+    Pass => "TEST-PASS",
+    Fail => "TEST-UNEXPECTED-FAIL",
+    Skip => "TEST-SKIP",
+    Todo => "TEST-KNOWN-FAIL"
+  }
+}
+
+// TBPL lines can not span multiple lines.
+fn single_line(value: &str) -> String {
+  value.lines().collect::<Vec<&str>>().connect(" ")
+}
+
+impl Reporter for TbplReporter {
+  fn report(
+    &self,
+    suites: &Vec<Suite>,
+    results: &Vec<SuiteFileResult>,
+    out: &mut Writer
+  ) -> IoResult<()> {
+    // Counts of Pass, Fail, Todo and Skip.
+    let mut counts = [0u, 0u, 0u, 0u];
+    let mut current_suite = None;
+
+    for file in results.iter() {
+      if file.suite < suites.len() && current_suite != Some(file.suite) {
+        current_suite = Some(file.suite);
+        let ref suite = suites[file.suite];
+        try!(writeln!(
+          out,
+          "SUITE-START | {} ({}) | {} (#{})",
+          suite.group,
+          suite.root,
+          suite.manifest,
+          suite.manifest_index
+        ));
+      }
+
+      let path = format!("{}", file.result.path.display());
+      try!(writeln!(out, "TEST-START | {}", path));
+
+      let tests = file.result.test_results();
+      for test in tests.iter() {
+        let message = match test.message {
+          Some(ref message) => single_line(message.as_slice()),
+          None => file.group.clone()
+        };
+
+        try!(writeln!(
+          out, "{} | {} | {}", status_to_str(test), test.name, message
+        ));
+
+        let index = match test.status {
+          Pass => 0, Fail => 1, Todo => 2, Skip => 3
+        };
+        counts[index] += 1;
+      }
+
+      // Prefixed so output which looks like a result is never parsed as one.
+      if tests.iter().any(|test| test.failed()) {
+        for line in file.result.stderr.as_slice().lines() {
+          try!(writeln!(out, "PROCESS-OUTPUT | stderr | {}", line));
+        }
+      }
+
+      try!(writeln!(
+        out,
+        "TEST-END | {} | finished in {}ms",
+        path,
+        file.result.duration.num_milliseconds()
+      ));
+    }
+
+    try!(writeln!(out, "INFO | Passed: {}", counts[0]));
+    try!(writeln!(out, "INFO | Failed: {}", counts[1]));
+    try!(writeln!(out, "INFO | Todo: {}", counts[2]));
+    writeln!(out, "INFO | Skipped: {}", counts[3])
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use parser::{parse_all};
+  use parser::tbpl::{TbplParser};
+  use reporter::{Reporter};
+  use results::{TestResult, Pass, Fail, Todo};
+  use test::{get_suite, suite_file_result};
+  use std::io::{MemWriter};
+  use std::time::{Duration};
+  use super::{TbplReporter};
+
+  fn report(tests: Vec<TestResult>, code: int) -> String {
+    let mut file = suite_file_result(0, "/foo/test_a.js", code, tests);
+    file.result.stderr = "oops".to_string();
+    file.result.duration = Duration::milliseconds(7);
+    let results = vec![file];
+
+    let mut out = MemWriter::new();
+    let suites = vec![get_suite("unit", "/foo", &["*.js"])];
+    TbplReporter.report(&suites, &results, &mut out).unwrap();
+    String::from_utf8(out.unwrap()).unwrap()
+  }
+
+  #[test]
+  fn report_file_status() {
+    let expected = "SUITE-START | unit (/foo) | /foo/overlord.toml (#0)\n\
+                    TEST-START | /foo/test_a.js\n\
+                    TEST-PASS | /foo/test_a.js | unit\n\
+                    TEST-END | /foo/test_a.js | finished in 7ms\n\
+                    INFO | Passed: 1\n\
+                    INFO | Failed: 0\n\
+                    INFO | Todo: 0\n\
+                    INFO | Skipped: 0\n";
+    assert_eq!(report(Vec::new(), 0), expected.to_string());
+  }
+
+  #[test]
+  fn report_tests() {
+    let mut failed = TestResult::new("two".to_string(), Fail);
+    failed.message = Some("expected\n2".to_string());
+    let tests = vec![
+      TestResult::new("one".to_string(), Pass),
+      failed,
+      TestResult::new("three".to_string(), Todo)
+    ];
+
+    let expected = "SUITE-START | unit (/foo) | /foo/overlord.toml (#0)\n\
+                    TEST-START | /foo/test_a.js\n\
+                    TEST-PASS | one | unit\n\
+                    TEST-UNEXPECTED-FAIL | two | expected 2\n\
+                    TEST-KNOWN-FAIL | three | unit\n\
+                    PROCESS-OUTPUT | stderr | oops\n\
+                    TEST-END | /foo/test_a.js | finished in 7ms\n\
+                    INFO | Passed: 1\n\
+                    INFO | Failed: 1\n\
+                    INFO | Todo: 1\n\
+                    INFO | Skipped: 0\n";
+    assert_eq!(report(tests, 1), expected.to_string());
+  }
+
+  #[test]
+  fn round_trip() {
+    let mut failed = TestResult::new("two".to_string(), Fail);
+    failed.message = Some("nope".to_string());
+    let output = report(vec![failed.clone()], 1);
+
+    let results = parse_all(&mut TbplParser::new(), output.as_slice());
+    assert_eq!(results, vec![failed]);
+  }
+
+  #[test]
+  fn round_trip_stderr_results() {
+    let mut failed = TestResult::new("two".to_string(), Fail);
+    failed.message = Some("nope".to_string());
+    let mut file = suite_file_result(0, "/foo/test_a.js", 1, vec![
+      failed.clone()
+    ]);
+    file.result.stderr = "TEST-UNEXPECTED-FAIL | other.js | fake".to_string();
+
+    let mut out = MemWriter::new();
+    TbplReporter.report(&Vec::new(), &vec![file], &mut out).unwrap();
+    let output = String::from_utf8(out.unwrap()).unwrap();
+
+    let results = parse_all(&mut TbplParser::new(), output.as_slice());
+    assert_eq!(results, vec![failed]);
+  }
+}
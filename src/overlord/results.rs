@@ -0,0 +1,65 @@
+// The structured result model shared by every output parser (TAP, TBPL, etc...)
+// and every reporter. Parsers convert raw output into these results and
+// reporters only ever deal with these results.
+use std::time::{Duration};
+
+#[deriving(Clone, PartialEq, Show)]
+pub enum TestStatus {
+  /// The test ran and passed.
+  Pass,
+
+  /// The test ran and failed (unexpectedly).
+  Fail,
+
+  /// The test was not run.
+  Skip,
+
+  /// The test is expected to fail (TAP TODO, known failures, etc...).
+  Todo
+}
+
+impl TestStatus {
+  /// Lower case name of the status (as used in JSON output).
+  pub fn name(&self) -> &'static str {
+    match *self {
+      Pass => "pass",
+      Fail => "fail",
+      Skip => "skip",
+      Todo => "todo"
+    }
+  }
+}
+
+/// The result of an individual test within a file.
+#[deriving(Clone, PartialEq, Show)]
+pub struct TestResult {
+  /// Human readable name of the test.
+  pub name: String,
+
+  pub status: TestStatus,
+
+  /// Reason given for the status (skip reason, failure message, etc...).
+  pub message: Option<String>,
+
+  /// Additional diagnostic output (for example a TAP YAML block).
+  pub diagnostics: Option<String>,
+
+  /// Time spent running the test if the format reports it.
+  pub duration: Option<Duration>
+}
+
+impl TestResult {
+  pub fn new(name: String, status: TestStatus) -> TestResult {
+    TestResult {
+      name: name,
+      status: status,
+      message: None,
+      diagnostics: None,
+      duration: None
+    }
+  }
+
+  pub fn failed(&self) -> bool {
+    self.status == Fail
+  }
+}
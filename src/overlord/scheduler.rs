@@ -0,0 +1,327 @@
+// Parallel execution of files across suites. Suites are expanded into
+// individual "jobs" (one per file) which are then pulled off of a shared queue
+// by a fixed number of worker tasks.
+use config::{Suite, Format};
+use executor::{execute_streaming, FileResult};
+use file_finder::{find_files};
+use error::{OverlordError, OverlordResult};
+use results::{TestResult};
+use std::sync::{Arc, Mutex};
+use std::cmp;
+use std::os;
+
+/// A single file which should be run by a single suite.
+#[deriving(Clone)]
+pub struct Job {
+  /// Index of the suite (in the list given to the scheduler) to run the file.
+  pub suite: uint,
+
+  /// File to run.
+  pub file: Path
+}
+
+/// The result of running a single file which can be attributed to the suite
+/// it was run by.
+pub struct SuiteFileResult {
+  /// Index of the suite (in the list given to the scheduler).
+  pub suite: uint,
+
+  /// Group of the suite the file was run by.
+  pub group: String,
+
+  /// Result of running the file.
+  pub result: FileResult
+}
+
+/// Events sent by the workers while jobs are running. Each event carries the
+/// index of the job (in the list given to `run_jobs`) it belongs to. Events
+/// for different jobs are interleaved in the order they happen.
+#[deriving(Clone)]
+pub enum RunEvent {
+  /// A worker started running the job.
+  JobStarted(uint),
+
+  /// A single test of the job finished.
+  JobTest(uint, TestResult),
+
+  /// The job finished (this is always the last event for a job).
+  JobFinished(uint, OverlordResult<FileResult>)
+}
+
+impl RunEvent {
+  /// Index of the job the event belongs to.
+  pub fn job(&self) -> uint {
+    match *self {
+      JobStarted(job) | JobTest(job, _) | JobFinished(job, _) => job
+    }
+  }
+
+  pub fn is_finished(&self) -> bool {
+    match *self {
+      JobFinished(..) => true,
+      _ => false
+    }
+  }
+}
+
+// Jobs must be converted into fully owned values before they can be sent to
+// another task.
+struct WorkItem {
+  index: uint,
+  executable: String,
+  format: Format,
+  cwd: Path,
+  file: Path
+}
+
+/// Expand the suites into a job for every file they match. Jobs are ordered by
+/// suite then by file.
+pub fn expand(suites: &Vec<Suite>) -> OverlordResult<Vec<Job>> {
+  expand_suites(suites, &range(0, suites.len()).collect())
+}
+
+/// Find every file for only the given suites (by index).
+pub fn expand_suites(
+  suites: &Vec<Suite>, indexes: &Vec<uint>
+) -> OverlordResult<Vec<Job>> {
+  let mut jobs = Vec::new();
+  for &index in indexes.iter() {
+    let files = try!(find_files(&suites[index]));
+    for file in files.move_iter() {
+      jobs.push(Job { suite: index, file: file });
+    }
+  }
+  Ok(jobs)
+}
+
+pub struct Scheduler {
+  /// Number of files which may be run concurrently (`-j`).
+  pub jobs: uint
+}
+
+impl Scheduler {
+  /// Create a scheduler which runs as many files concurrently as there are
+  /// cpus.
+  pub fn new() -> Scheduler {
+    Scheduler::with_jobs(os::num_cpus())
+  }
+
+  /// Create a scheduler with a specific number of concurrent workers. At least
+  /// one worker is always used.
+  pub fn with_jobs(jobs: uint) -> Scheduler {
+    Scheduler { jobs: cmp::max(jobs, 1) }
+  }
+
+  /// Run every file in every suite.
+  pub fn run(
+    &self, suites: &Vec<Suite>
+  ) -> OverlordResult<Vec<SuiteFileResult>> {
+    let jobs = try!(expand(suites));
+    self.run_jobs(suites, jobs)
+  }
+
+  /// Run the given jobs. Results are returned in the same order as the jobs
+  /// regardless of the order in which they completed. Files which could not
+  /// be run are included as failing results.
+  pub fn run_jobs(
+    &self, suites: &Vec<Suite>, jobs: Vec<Job>
+  ) -> OverlordResult<Vec<SuiteFileResult>> {
+    self.run_jobs_with(suites, jobs, |_| {})
+  }
+
+  /// Same as `run_jobs` but `on_event` is called (from the calling task) for
+  /// every event as soon as it is received from the workers.
+  pub fn run_jobs_with(
+    &self, suites: &Vec<Suite>, jobs: Vec<Job>, on_event: |&RunEvent|
+  ) -> OverlordResult<Vec<SuiteFileResult>> {
+    let total = jobs.len();
+
+    // Note the reverse so popping off the queue runs jobs in order.
+    let pending = jobs.iter().enumerate().rev();
+    let work: Vec<WorkItem> = pending.map(|(index, job)| {
+      let suite = &suites[job.suite];
+      WorkItem {
+        index: index,
+        executable: suite.executable.clone(),
+        format: suite.format,
+        cwd: suite.root.get().clone(),
+        file: job.file.clone()
+      }
+    }).collect();
+
+    let queue = Arc::new(Mutex::new(work));
+    let (tx, rx) = channel();
+
+    for _ in range(0, cmp::min(self.jobs, total)) {
+      let queue = queue.clone();
+      let tx = tx.clone();
+      spawn(proc() {
+        loop {
+          // The lock must be released before running the file so other
+          // workers can make progress.
+          let item = {
+            let mut pending = queue.lock();
+            pending.pop()
+          };
+
+          match item {
+            Some(item) => {
+              let index = item.index;
+              tx.send(JobStarted(index));
+              let result = execute_streaming(
+                item.executable.as_slice(),
+                item.format,
+                &item.cwd,
+                &item.file,
+                |test| tx.send(JobTest(index, test.clone()))
+              );
+              tx.send(JobFinished(index, result));
+            },
+            None => break
+          }
+        }
+      });
+    }
+
+    // Only the workers hold senders now so the receiver closes once all of
+    // them are finished.
+    drop(tx);
+
+    let mut completed: Vec<(uint, OverlordResult<FileResult>)> = Vec::new();
+    for event in rx.iter() {
+      on_event(&event);
+      match event {
+        JobFinished(index, result) => completed.push((index, result)),
+        _ => {}
+      }
+    }
+
+    if completed.len() != total {
+      return Err(OverlordError::new(format!(
+        "Only {} of {} files finished running", completed.len(), total
+      )));
+    }
+
+    completed.sort_by(|&(a, _), &(b, _)| a.cmp(&b));
+
+    // A file which could not be run is reported as a failure rather than
+    // discarding the results of every other file.
+    let mut results = Vec::with_capacity(total);
+    for ((_, result), job) in completed.move_iter().zip(jobs.iter()) {
+      let result = match result {
+        Ok(result) => result,
+        Err(error) => FileResult::from_error(&job.file, error)
+      };
+      results.push(SuiteFileResult {
+        suite: job.suite,
+        group: suites[job.suite].group.clone(),
+        result: result
+      });
+    }
+    Ok(results)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use config::{select_group};
+  use config_loader::{import};
+  use test::{assert_path_eq};
+  use std::os;
+  use super::{Scheduler, RunEvent, JobStarted, JobTest, JobFinished, expand};
+
+  #[test]
+  fn default_jobs() {
+    assert_eq!(Scheduler::new().jobs, os::num_cpus());
+  }
+
+  #[test]
+  fn at_least_one_job() {
+    assert_eq!(Scheduler::with_jobs(0).jobs, 1);
+  }
+
+  #[test]
+  fn expand_suites() {
+    let suites = import(Path::new("test/multimanifest/overlord.toml")).unwrap();
+    let jobs = expand(&suites).unwrap();
+
+    // The deepest suite does not match any files.
+    assert_eq!(jobs.len(), 3);
+    assert_eq!(jobs[0].suite, 0);
+    assert_path_eq(&Path::new("test/multimanifest/1/1.txt"), &jobs[0].file);
+    assert_eq!(jobs[2].suite, 2);
+    assert_path_eq(
+      &Path::new("test/multimanifest/nested/nest.txt"), &jobs[2].file
+    );
+  }
+
+  #[test]
+  fn expand_group_across_manifests() {
+    let suites = import(Path::new("test/groups/overlord.toml")).unwrap();
+    let indexes = select_group(&suites, "unit").unwrap();
+    let jobs = super::expand_suites(&suites, &indexes).unwrap();
+
+    // The lint suite in the same manifest is not included.
+    assert_eq!(jobs.len(), 2);
+    assert_eq!(jobs[0].suite, 0);
+    assert_path_eq(&Path::new("test/groups/a.txt"), &jobs[0].file);
+    assert_eq!(jobs[1].suite, 2);
+    assert_path_eq(&Path::new("test/groups/nested/b.txt"), &jobs[1].file);
+  }
+
+  #[test]
+  fn run_in_parallel() {
+    let suites = import(Path::new("test/multimanifest/overlord.toml")).unwrap();
+    let results = Scheduler::with_jobs(2).run(&suites).unwrap();
+
+    assert_eq!(results.len(), 3);
+    for (index, file_result) in results.iter().enumerate() {
+      assert_eq!(file_result.suite, index);
+      assert_eq!(file_result.group, "unit".to_string());
+      assert!(file_result.result.success());
+    }
+
+    assert_path_eq(
+      &Path::new("test/multimanifest/2/2.txt"), &results[1].result.path
+    );
+  }
+
+  #[test]
+  fn run_with_missing_executable() {
+    let suites = import(Path::new("test/broken/overlord.toml")).unwrap();
+    let results = Scheduler::with_jobs(2).run(&suites).unwrap();
+
+    // The file of the broken suite fails without losing the other result.
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].group, "broken".to_string());
+    assert!(!results[0].result.success());
+    assert_eq!(results[0].result.tests.len(), 1);
+    assert!(results[0].result.tests[0].failed());
+    assert_eq!(results[1].group, "unit".to_string());
+    assert!(results[1].result.success());
+  }
+
+  #[test]
+  fn run_no_files() {
+    let results = Scheduler::new().run(&Vec::new()).unwrap();
+    assert_eq!(results.len(), 0);
+  }
+
+  #[test]
+  fn run_events() {
+    let suites = import(Path::new("test/formats/overlord.toml")).unwrap();
+    let jobs = expand(&suites).unwrap();
+    let mut events: Vec<RunEvent> = Vec::new();
+    let results = Scheduler::with_jobs(1).run_jobs_with(&suites, jobs, |event| {
+      events.push(event.clone());
+    }).unwrap();
+
+    assert_eq!(results.len(), 1);
+    // Started, three tests then finished.
+    assert_eq!(events.len(), 5);
+    assert!(match events[0] { JobStarted(0) => true, _ => false });
+    assert!(match events[1] { JobTest(0, _) => true, _ => false });
+    assert!(events[4].is_finished());
+    assert!(match events[4] { JobFinished(0, Ok(_)) => true, _ => false });
+  }
+}
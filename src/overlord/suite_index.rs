@@ -0,0 +1,178 @@
+// Prebuilt index for matching paths to suites in large trees. Suite roots are
+// stored in a trie keyed by path component so finding the deepest root of a
+// path only visits one node per component of the path (rather than every
+// suite like `path_identifier::identify`).
+use config::{Suite};
+use std::collections::hashmap::{HashMap, HashSet};
+
+struct IndexNode {
+  children: HashMap<Vec<u8>, IndexNode>,
+
+  // Suites (by index, in import order) with a root at this node.
+  suites: Vec<uint>
+}
+
+impl IndexNode {
+  fn new() -> IndexNode {
+    IndexNode { children: HashMap::new(), suites: Vec::new() }
+  }
+}
+
+pub struct SuiteIndex<'a> {
+  suites: &'a Vec<Suite<'a>>,
+
+  // Absolute and relative roots never match each other.
+  absolute: IndexNode,
+  relative: IndexNode
+}
+
+impl<'a> SuiteIndex<'a> {
+  /// Index the roots (including roots added by extending manifests) of every
+  /// suite.
+  pub fn new(suites: &'a Vec<Suite<'a>>) -> SuiteIndex<'a> {
+    let mut index = SuiteIndex {
+      suites: suites,
+      absolute: IndexNode::new(),
+      relative: IndexNode::new()
+    };
+
+    for (position, suite) in suites.iter().enumerate() {
+      for root in suite.roots().iter() {
+        let mut node = if root.is_absolute() {
+          &mut index.absolute
+        } else {
+          &mut index.relative
+        };
+
+        for component in root.components() {
+          let current = node;
+          node = current.children.find_or_insert_with(
+            component.to_vec(), |_| IndexNode::new()
+          );
+        }
+
+        if !node.suites.contains(&position) {
+          node.suites.push(position);
+        }
+      }
+    }
+    index
+  }
+
+  /// Same as `path_identifier::identify`: the deepest suite which contains
+  /// the path falling back to the suites of ancestor roots.
+  pub fn identify(&self, path: &Path) -> Option<&'a Suite<'a>> {
+    self.identify_all(path).move_iter().next()
+  }
+
+  /// Same as `path_identifier::identify_all`: every suite which contains the
+  /// path from the deepest root to the shallowest.
+  pub fn identify_all(&self, path: &Path) -> Vec<&'a Suite<'a>> {
+    let mut node = if path.is_absolute() {
+      &self.absolute
+    } else {
+      &self.relative
+    };
+
+    // Every node along the path from the top down.
+    let mut ancestors = vec![node];
+    for component in path.components() {
+      node = match node.children.find(&component.to_vec()) {
+        Some(child) => child,
+        None => break
+      };
+      ancestors.push(node);
+    }
+
+    // Suites with several roots are only as deep as their deepest root.
+    let mut seen = HashSet::new();
+    let mut found = Vec::new();
+    for node in ancestors.iter().rev() {
+      for position in node.suites.iter() {
+        if !seen.insert(*position) {
+          continue
+        }
+
+        let suite = &self.suites[*position];
+        if suite.contains_path(path) {
+          found.push(suite);
+        }
+      }
+    }
+    found
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use config::{Suite, SuitePath};
+  use path_identifier::{identify, identify_all};
+  use test::{get_suites};
+  use super::{SuiteIndex};
+
+  #[test]
+  fn index_deepest_root() {
+    let suites = get_suites(vec!["/a", "/a/b/c", "/a/b", "/x"]);
+    let index = SuiteIndex::new(&suites);
+
+    assert_eq!(index.identify(&Path::new("/a/1.txt")).unwrap(), &suites[0]);
+    assert_eq!(index.identify(&Path::new("/a/b/1.txt")).unwrap(), &suites[2]);
+    assert_eq!(
+      index.identify(&Path::new("/a/b/c/1.txt")).unwrap(), &suites[1]
+    );
+    assert_eq!(index.identify(&Path::new("/y/1.txt")), None);
+    assert_eq!(index.identify(&Path::new("a/1.txt")), None);
+  }
+
+  #[test]
+  fn index_sibling_roots() {
+    // Roots which share a prefix but not a directory.
+    let suites = get_suites(vec!["/a/bc", "/a/b"]);
+    let index = SuiteIndex::new(&suites);
+
+    assert_eq!(index.identify(&Path::new("/a/b/1.txt")).unwrap(), &suites[1]);
+    assert_eq!(
+      index.identify(&Path::new("/a/bc/1.txt")).unwrap(), &suites[0]
+    );
+  }
+
+  #[test]
+  fn index_first_suite_wins() {
+    let suites = get_suites(vec!["/a", "/a"]);
+    let index = SuiteIndex::new(&suites);
+    let found = index.identify(&Path::new("/a/1.txt")).unwrap();
+    assert!(found as *const Suite == &suites[0] as *const Suite);
+  }
+
+  #[test]
+  fn index_falls_back_to_ancestor() {
+    let mut suites = get_suites(vec!["/a", "/a/b"]);
+    suites[0].paths.push(
+      SuitePath::new(&suites[0].root, "b/*.js".to_string())
+    );
+    let index = SuiteIndex::new(&suites);
+
+    assert_eq!(index.identify(&Path::new("/a/b/1.js")).unwrap(), &suites[0]);
+    assert_eq!(
+      index.identify_all(&Path::new("/a/b/1.txt")), vec![&suites[1]]
+    );
+  }
+
+  #[test]
+  fn index_matches_linear_scan() {
+    let mut suites = get_suites(vec!["/a", "/a/b", "/a/b/c", "/a/d"]);
+    suites[0].paths.push(
+      SuitePath::new(&suites[0].root, "**/*.txt".to_string())
+    );
+    let index = SuiteIndex::new(&suites);
+
+    let paths = [
+      "/a/1.txt", "/a/b/1.js", "/a/b/c/d/1.txt", "/a/d/1.txt", "/b/1.txt"
+    ];
+    for path in paths.iter() {
+      let path = Path::new(*path);
+      assert_eq!(index.identify(&path), identify(&path, &suites));
+      assert_eq!(index.identify_all(&path), identify_all(&path, &suites));
+    }
+  }
+}
@@ -1,4 +1,10 @@
+use config::{Suite, SuitePath, ExitCodeFormat};
+use executor::{FileResult};
+use results::{TestResult};
+use scheduler::{SuiteFileResult};
+use std::io::process::{ExitStatus};
 use std::os;
+use std::time::{Duration};
 use util::{PathWrapper};
 
 pub fn assert_path_wrapper_eq(expected: &Path, actual: &PathWrapper) {
@@ -16,3 +22,50 @@ pub fn assert_path_eq(expected: &Path, actual: &Path) {
     )
   );
 }
+
+/// Suite (run with `cat`) of the files matching `paths` within `root`. Tests
+/// override any other fields they care about.
+pub fn get_suite<'a>(group: &str, root: &str, paths: &[&str]) -> Suite<'a> {
+  let root = PathWrapper::from_str(root);
+  let paths = paths.iter().map(|path| {
+    SuitePath::new(&root, path.to_string())
+  }).collect();
+
+  Suite {
+    group: group.to_string(),
+    name: None,
+    title: None,
+    manifest: PathWrapper::new(root.get().join("overlord.toml")),
+    manifest_index: 0,
+    exclude: Vec::new(),
+    respect_ignore_files: true,
+    root: root,
+    paths: paths,
+    executable: "cat".to_string(),
+    format: ExitCodeFormat
+  }
+}
+
+/// One suite of the `*.txt` files in each root (in the same order).
+pub fn get_suites<'a>(roots: Vec<&str>) -> Vec<Suite<'a>> {
+  roots.iter().map(|root| get_suite("xfoo", *root, &["*.txt"])).collect()
+}
+
+/// Result of running the file with the suite (in the "unit" group) without
+/// any output.
+pub fn suite_file_result(
+  suite: uint, path: &str, code: int, tests: Vec<TestResult>
+) -> SuiteFileResult {
+  SuiteFileResult {
+    suite: suite,
+    group: "unit".to_string(),
+    result: FileResult {
+      path: Path::new(path),
+      status: ExitStatus(code),
+      stdout: String::new(),
+      stderr: String::new(),
+      duration: Duration::zero(),
+      tests: tests
+    }
+  }
+}
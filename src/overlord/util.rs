@@ -3,7 +3,7 @@
 use std::fmt::{Show, Formatter, FormatError};
 
 // A single layer of indirection around a path simply so we can
-#[deriving(PartialEq)]
+#[deriving(Clone, PartialEq)]
 pub struct PathWrapper {
   value: Path
 }